@@ -20,6 +20,19 @@ fn main() {
         println!("cargo:rustc-link-arg=src/main.o");
     }
 
+    // The `command` and `reactor` worlds import the filesystem and sockets
+    // interfaces unless they're opted out of. Features can only be added, so
+    // the opt-outs are features of their own, and the result is a cfg.
+    for interface in ["filesystem", "sockets"] {
+        let feature = interface.to_uppercase();
+        println!("cargo:rustc-check-cfg=cfg({interface})");
+        if env::var_os(format!("CARGO_FEATURE_{feature}")).is_some()
+            && env::var_os(format!("CARGO_FEATURE_NO_{feature}")).is_none()
+        {
+            println!("cargo:rustc-cfg={interface}");
+        }
+    }
+
     // Some specific flags to `wasm-ld` to inform the shape of this adapter.
     // Notably we're importing memory from the main module and additionally our
     // own module has no stack at all since it's specifically allocated at
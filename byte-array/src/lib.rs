@@ -30,6 +30,177 @@ pub fn str_nl(input: TokenStream) -> TokenStream {
     .collect()
 }
 
+/// Expand a format string and its arguments into calls to a printing
+/// function, without creating any static data.
+///
+/// The first argument is the function to call with each piece, of type
+/// `fn(&[u8])`. Literal pieces of the format string are passed as byte
+/// arrays, like `str`, and each argument is formatted into a byte array on
+/// the stack according to its placeholder:
+///
+/// - `{}` prints an unsigned integer, of up to 64 bits, in decimal.
+/// - `{:x}` prints an unsigned integer, of up to 64 bits, in hexadecimal.
+/// - `{:?}` prints a `&[u8]` between quotes.
+///
+/// `{{` and `}}` print literal braces. Arguments are separated by commas at
+/// the top level, so an argument containing a comma, such as a generic type,
+/// must be parenthesized.
+///
+/// ```ignore
+/// byte_array::format!(print, "bad fd {} at {:x}: {:?}\n", fd, ptr, path);
+/// ```
+#[proc_macro]
+pub fn format(input: TokenStream) -> TokenStream {
+    let mut args = split_args(input).into_iter();
+    let print = args.next().expect("missing printing function");
+    let fmt = match args.next().as_deref() {
+        Some([TokenTree::Literal(l)]) => to_string(l.clone()),
+        _ => panic!("expected a format string"),
+    };
+
+    let mut rv = Vec::new();
+    let mut literal = String::new();
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut spec = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => spec.push(c),
+                        None => panic!("unterminated placeholder"),
+                    }
+                }
+                let arg = args.next().expect("missing argument for placeholder");
+
+                print_literal(&mut rv, &print, &mut literal);
+                let code = match spec.as_str() {
+                    "" => format_int(&print, &arg, 10, 20),
+                    ":x" => format_int(&print, &arg, 16, 16),
+                    ":?" => format_bytes(&print, &arg),
+                    _ => panic!("unsupported placeholder"),
+                };
+                rv.extend(code);
+            }
+            '}' => panic!("unmatched `}}` in format string"),
+            c => literal.push(c),
+        }
+    }
+    print_literal(&mut rv, &print, &mut literal);
+
+    assert!(args.next().is_none(), "unused format argument");
+
+    vec![TokenTree::Group(Group::new(
+        Delimiter::Brace,
+        rv.into_iter().collect(),
+    ))]
+    .into_iter()
+    .collect()
+}
+
+/// Split the input on top-level commas, ignoring a trailing comma.
+fn split_args(input: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut args = vec![Vec::new()];
+    for token in input {
+        match token {
+            TokenTree::Punct(p) if p.as_char() == ',' => args.push(Vec::new()),
+            token => args.last_mut().unwrap().push(token),
+        }
+    }
+    if args.last().unwrap().is_empty() {
+        args.pop();
+    }
+    args
+}
+
+/// Append `print(&[...]);` for the pending literal piece, if any.
+fn print_literal(rv: &mut Vec<TokenTree>, print: &[TokenTree], literal: &mut String) {
+    if literal.is_empty() {
+        return;
+    }
+    let mut bytes = Vec::new();
+    for b in std::mem::take(literal).into_bytes() {
+        bytes.push(TokenTree::Literal(Literal::u8_suffixed(b)));
+        bytes.push(TokenTree::Punct(Punct::new(',', Spacing::Alone)));
+    }
+    let array = TokenTree::Group(Group::new(Delimiter::Bracket, bytes.into_iter().collect()));
+    let mut call = print.to_vec();
+    call.push(TokenTree::Group(Group::new(
+        Delimiter::Parenthesis,
+        [TokenTree::Punct(Punct::new('&', Spacing::Alone)), array]
+            .into_iter()
+            .collect(),
+    )));
+    call.push(TokenTree::Punct(Punct::new(';', Spacing::Alone)));
+    rv.extend(call);
+}
+
+/// Generate code printing the integer `arg` in `radix`, using a stack buffer
+/// of `digits` bytes. Leading zeros are stripped with slice patterns rather
+/// than indexing, to avoid bounds checks and their panic messages.
+fn format_int(print: &[TokenTree], arg: &[TokenTree], radix: u64, digits: usize) -> Vec<TokenTree> {
+    let template = std::format!(
+        "{{
+            let mut value = (ARG) as u64;
+            let mut digits = [0u8; {digits}];
+            for digit in digits.iter_mut().rev() {{
+                let n = (value % {radix}) as u8;
+                *digit = if n < 10 {{ b'0' + n }} else {{ b'a' + n - 10 }};
+                value /= {radix};
+            }}
+            let mut rest: &[u8] = &digits;
+            while let [b'0', tail @ ..] = rest {{
+                if tail.is_empty() {{
+                    break;
+                }}
+                rest = tail;
+            }}
+            PRINT(rest);
+        }}"
+    );
+    substitute(template.parse().unwrap(), print, arg)
+}
+
+/// Generate code printing the byte slice `arg` between quotes.
+fn format_bytes(print: &[TokenTree], arg: &[TokenTree]) -> Vec<TokenTree> {
+    let template = "{
+        PRINT(&[b'\"']);
+        PRINT(ARG);
+        PRINT(&[b'\"']);
+    }";
+    substitute(template.parse().unwrap(), print, arg)
+}
+
+/// Replace the `PRINT` and `ARG` identifiers in `template` with the tokens
+/// given to the macro. This preserves the tokens as-is, including `$crate`
+/// paths which wouldn't survive a round trip through a string.
+fn substitute(template: TokenStream, print: &[TokenTree], arg: &[TokenTree]) -> Vec<TokenTree> {
+    let mut rv = Vec::new();
+    for token in template {
+        match token {
+            TokenTree::Ident(i) if i.to_string() == "PRINT" => rv.extend(print.iter().cloned()),
+            TokenTree::Ident(i) if i.to_string() == "ARG" => rv.extend(arg.iter().cloned()),
+            TokenTree::Group(g) => {
+                let stream = substitute(g.stream(), print, arg);
+                let mut group = Group::new(g.delimiter(), stream.into_iter().collect());
+                group.set_span(g.span());
+                rv.push(TokenTree::Group(group));
+            }
+            token => rv.push(token),
+        }
+    }
+    rv
+}
+
 fn convert_str(input: TokenStream) -> Vec<TokenTree> {
     let mut it = input.into_iter();
 
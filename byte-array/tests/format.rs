@@ -0,0 +1,38 @@
+use std::cell::RefCell;
+
+thread_local! {
+    static OUTPUT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+fn print(bytes: &[u8]) {
+    OUTPUT.with(|output| output.borrow_mut().extend_from_slice(bytes));
+}
+
+fn take() -> String {
+    OUTPUT.with(|output| String::from_utf8(output.take()).unwrap())
+}
+
+#[test]
+fn literal() {
+    byte_array::format!(print, "hello {{world}}\n");
+    assert_eq!(take(), "hello {world}\n");
+}
+
+#[test]
+fn decimal() {
+    byte_array::format!(print, "{} {} {}", 0u32, 1234u32, u64::MAX);
+    assert_eq!(take(), "0 1234 18446744073709551615");
+}
+
+#[test]
+fn hex() {
+    byte_array::format!(print, "{:x} {:x} {:x}", 0u8, 0xdead_beefu32, u64::MAX);
+    assert_eq!(take(), "0 deadbeef ffffffffffffffff");
+}
+
+#[test]
+fn bytes() {
+    let path: &[u8] = b"a/b";
+    byte_array::format!(print, "open {:?} at fd {}", path, 3 + 1,);
+    assert_eq!(take(), "open \"a/b\" at fd 4");
+}
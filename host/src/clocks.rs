@@ -1,6 +1,7 @@
 #![allow(unused_variables)]
 
 use crate::command::wasi::{
+    cpu_clock,
     monotonic_clock::{self, Instant},
     poll::Pollable,
     timezone::{self, Timezone, TimezoneDisplay},
@@ -9,6 +10,7 @@ use crate::command::wasi::{
 use crate::poll::PollableEntry;
 use crate::WasiCtx;
 use cap_std::time::SystemTime;
+use wasmtime::{CallHook, Store};
 
 impl TryFrom<SystemTime> for Datetime {
     type Error = anyhow::Error;
@@ -60,6 +62,34 @@ impl monotonic_clock::Host for WasiCtx {
     }
 }
 
+/// Run the `cpu-clock` of the `WasiCtx` returned by `get` while the store's
+/// instance is running wasm, or a host function it called, so that the clock
+/// measures the instance's CPU time.
+pub fn track_cpu_time<T>(
+    store: &mut Store<T>,
+    get: impl Fn(&mut T) -> &mut WasiCtx + Send + Sync + 'static,
+) {
+    store.call_hook(move |data, hook| {
+        let cpu = &get(data).clocks.cpu;
+        match hook {
+            CallHook::CallingWasm | CallHook::ReturningFromHost => cpu.start(),
+            CallHook::ReturningFromWasm | CallHook::CallingHost => cpu.stop(),
+        }
+        Ok(())
+    });
+}
+
+#[async_trait::async_trait]
+impl cpu_clock::Host for WasiCtx {
+    async fn now(&mut self) -> anyhow::Result<Instant> {
+        Ok(self.clocks.cpu.now())
+    }
+
+    async fn resolution(&mut self) -> anyhow::Result<Instant> {
+        Ok(self.clocks.cpu.resolution())
+    }
+}
+
 #[async_trait::async_trait]
 impl timezone::Host for WasiCtx {
     async fn display(
@@ -19,6 +19,7 @@ pub fn add_to_linker<T: Send>(
 ) -> anyhow::Result<()> {
     wasi::wall_clock::add_to_linker(l, f)?;
     wasi::monotonic_clock::add_to_linker(l, f)?;
+    wasi::cpu_clock::add_to_linker(l, f)?;
     wasi::timezone::add_to_linker(l, f)?;
     wasi::filesystem::add_to_linker(l, f)?;
     wasi::poll::add_to_linker(l, f)?;
@@ -34,5 +35,7 @@ pub fn add_to_linker<T: Send>(
     wasi::exit::add_to_linker(l, f)?;
     wasi::environment::add_to_linker(l, f)?;
     wasi::preopens::add_to_linker(l, f)?;
+    wasi::terminal_input::add_to_linker(l, f)?;
+    wasi::terminal_output::add_to_linker(l, f)?;
     Ok(())
 }
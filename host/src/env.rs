@@ -1,5 +1,6 @@
 use crate::command::wasi;
 use crate::WasiCtx;
+use wasi_common::stream::TableStreamExt;
 
 #[async_trait::async_trait]
 impl wasi::environment::Host for WasiCtx {
@@ -20,6 +21,14 @@ impl wasi::preopens::Host for WasiCtx {
             stderr: 2,
         })
     }
+    async fn get_stdio_types(&mut self) -> Result<wasi::preopens::StdioTypes, anyhow::Error> {
+        let table = self.table();
+        Ok(wasi::preopens::StdioTypes {
+            stdin: table.get_input_stream(0)?.get_filetype().await?.into(),
+            stdout: table.get_output_stream(1)?.get_filetype().await?.into(),
+            stderr: table.get_output_stream(2)?.get_filetype().await?.into(),
+        })
+    }
     async fn get_directories(
         &mut self,
     ) -> Result<Vec<(wasi::filesystem::Descriptor, String)>, anyhow::Error> {
@@ -31,4 +40,8 @@ impl wasi::preopens::Host for WasiCtx {
         }
         Ok(results)
     }
+
+    async fn get_sockets(&mut self) -> Result<Vec<wasi::tcp::TcpSocket>, anyhow::Error> {
+        Ok(self.preopened_sockets.clone())
+    }
 }
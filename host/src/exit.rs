@@ -10,4 +10,11 @@ impl exit::Host for WasiCtx {
         };
         Err(anyhow::anyhow!(wasi_common::I32Exit(status)))
     }
+
+    async fn exit_with_code(&mut self, status_code: u32) -> anyhow::Result<()> {
+        // Preview1 exit codes are unsigned, but `I32Exit` is signed. Keep the
+        // bit pattern; the runner decides what to do with codes the platform
+        // can't represent.
+        Err(anyhow::anyhow!(wasi_common::I32Exit(status_code as i32)))
+    }
 }
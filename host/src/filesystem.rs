@@ -270,6 +270,17 @@ impl wasi::filesystem::Host for WasiCtx {
         Err(wasi::filesystem::ErrorCode::Unsupported.into())
     }
 
+    async fn allocate(
+        &mut self,
+        fd: wasi::filesystem::Descriptor,
+        offset: wasi::filesystem::Filesize,
+        len: wasi::filesystem::Filesize,
+    ) -> Result<(), wasi::filesystem::Error> {
+        let f = self.table_mut().get_file_mut(fd)?;
+        f.allocate(offset, len).await?;
+        Ok(())
+    }
+
     async fn set_size(
         &mut self,
         fd: wasi::filesystem::Descriptor,
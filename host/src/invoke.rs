@@ -0,0 +1,319 @@
+//! Calling arbitrary component exports with arguments given as JSON.
+//!
+//! Values are mapped to and from JSON according to their component-model
+//! type:
+//!
+//!  - `bool`, integers, and floats are JSON booleans and numbers.
+//!  - `string` is a JSON string, and `char` a string of exactly one character.
+//!  - `list` and `tuple` are JSON arrays.
+//!  - `record` is a JSON object keyed by field name.
+//!  - `variant` is an object with a single key naming the case, such as
+//!    `{"some-case": 42}`. Cases without a payload may also be written as a
+//!    plain string, and are always printed that way.
+//!  - `enum` is a string naming the case.
+//!  - `union` is the payload itself; the first case it parses as is used.
+//!  - `option` is `null` for `none`, and the payload itself for `some`.
+//!  - `result` is `{"ok": payload}` or `{"err": payload}`, with `null` for
+//!    a missing payload.
+//!  - `flags` is an array of the names of the flags which are set.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{Map, Number, Value};
+use wasmtime::{
+    component::{Func, Instance, Type, Val},
+    AsContextMut,
+};
+
+/// Find the function exported as `name` from `instance`. Functions exported
+/// from an exported instance are named with a path, such as
+/// `interface/function`.
+pub fn find_export(mut store: impl AsContextMut, instance: &Instance, name: &str) -> Result<Func> {
+    let mut store = store.as_context_mut();
+    let mut exports = instance.exports(&mut store);
+    let mut exports = exports.root();
+    let mut path = name.split('/').peekable();
+    while let Some(segment) = path.next() {
+        if path.peek().is_none() {
+            return exports
+                .func(segment)
+                .ok_or_else(|| anyhow!("no function `{segment}` exported in `{name}`"));
+        }
+        exports = exports
+            .into_instance(segment)
+            .ok_or_else(|| anyhow!("no instance `{segment}` exported in `{name}`"))?;
+    }
+    bail!("empty export name")
+}
+
+/// Call `func` with arguments parsed from `args`, a JSON array with one
+/// element per parameter, and return its results.
+pub async fn call<T: Send>(
+    mut store: impl AsContextMut<Data = T>,
+    func: &Func,
+    args: &str,
+) -> Result<Vec<Val>> {
+    let args: Value = serde_json::from_str(args).context("arguments are not valid JSON")?;
+    let args = match args {
+        Value::Array(args) => args,
+        _ => bail!("arguments must be a JSON array"),
+    };
+
+    let params = func.params(&store);
+    if args.len() != params.len() {
+        bail!(
+            "expected {} arguments, but {} were given",
+            params.len(),
+            args.len()
+        );
+    }
+    let params = params
+        .iter()
+        .zip(&args)
+        .enumerate()
+        .map(|(i, (ty, arg))| from_json(ty, arg).with_context(|| format!("in argument {i}")))
+        .collect::<Result<Vec<_>>>()?;
+
+    // The results are overwritten by the call; the initial values are only
+    // placeholders.
+    let mut results = vec![Val::Bool(false); func.results(&store).len()];
+    func.call_async(&mut store, &params, &mut results).await?;
+    func.post_return_async(&mut store).await?;
+    Ok(results)
+}
+
+/// Parse a value of type `ty` from JSON.
+pub fn from_json(ty: &Type, json: &Value) -> Result<Val> {
+    fn int<T: TryFrom<i64> + TryFrom<u64>>(json: &Value) -> Result<T> {
+        let n = match json {
+            Value::Number(n) => n,
+            _ => bail!("expected an integer, found {json}"),
+        };
+        let n = if let Some(n) = n.as_u64() {
+            T::try_from(n).ok()
+        } else if let Some(n) = n.as_i64() {
+            T::try_from(n).ok()
+        } else {
+            bail!("expected an integer, found {json}")
+        };
+        n.ok_or_else(|| anyhow!("integer {json} is out of range"))
+    }
+
+    fn float(json: &Value) -> Result<f64> {
+        json.as_f64()
+            .ok_or_else(|| anyhow!("expected a number, found {json}"))
+    }
+
+    fn str(json: &Value) -> Result<&str> {
+        json.as_str()
+            .ok_or_else(|| anyhow!("expected a string, found {json}"))
+    }
+
+    fn array(json: &Value) -> Result<&[Value]> {
+        json.as_array()
+            .map(Vec::as_slice)
+            .ok_or_else(|| anyhow!("expected an array, found {json}"))
+    }
+
+    /// An object with exactly one key, as used for variants and results.
+    fn case(json: &Value) -> Result<(&str, &Value)> {
+        match json {
+            Value::Object(map) if map.len() == 1 => {
+                let (key, value) = map.iter().next().unwrap();
+                Ok((key, value))
+            }
+            _ => bail!("expected an object with a single key, found {json}"),
+        }
+    }
+
+    Ok(match ty {
+        Type::Bool => Val::Bool(
+            json.as_bool()
+                .ok_or_else(|| anyhow!("expected a boolean, found {json}"))?,
+        ),
+        Type::S8 => Val::S8(int(json)?),
+        Type::U8 => Val::U8(int(json)?),
+        Type::S16 => Val::S16(int(json)?),
+        Type::U16 => Val::U16(int(json)?),
+        Type::S32 => Val::S32(int(json)?),
+        Type::U32 => Val::U32(int(json)?),
+        Type::S64 => Val::S64(int(json)?),
+        Type::U64 => Val::U64(int(json)?),
+        Type::Float32 => Val::Float32(float(json)? as f32),
+        Type::Float64 => Val::Float64(float(json)?),
+        Type::Char => {
+            let s = str(json)?;
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Val::Char(c),
+                _ => bail!("expected a single character, found {json}"),
+            }
+        }
+        Type::String => Val::String(str(json)?.into()),
+        Type::List(list) => {
+            let elem = list.ty();
+            let values = array(json)?
+                .iter()
+                .enumerate()
+                .map(|(i, json)| from_json(&elem, json).with_context(|| format!("in element {i}")))
+                .collect::<Result<Box<[_]>>>()?;
+            list.new_val(values)?
+        }
+        Type::Record(record) => {
+            let map = json
+                .as_object()
+                .ok_or_else(|| anyhow!("expected an object, found {json}"))?;
+            if let Some(key) = map
+                .keys()
+                .find(|key| !record.fields().any(|field| field.name == key.as_str()))
+            {
+                bail!("unknown field `{key}`");
+            }
+            let values = record
+                .fields()
+                .map(|field| {
+                    let json = map
+                        .get(field.name)
+                        .ok_or_else(|| anyhow!("missing field `{}`", field.name))?;
+                    let value = from_json(&field.ty, json)
+                        .with_context(|| format!("in field `{}`", field.name))?;
+                    Ok((field.name, value))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            record.new_val(values)?
+        }
+        Type::Tuple(tuple) => {
+            let json = array(json)?;
+            if json.len() != tuple.types().len() {
+                bail!(
+                    "expected a tuple of {} elements, found {}",
+                    tuple.types().len(),
+                    json.len()
+                );
+            }
+            let values = tuple
+                .types()
+                .zip(json)
+                .enumerate()
+                .map(|(i, (ty, json))| {
+                    from_json(&ty, json).with_context(|| format!("in element {i}"))
+                })
+                .collect::<Result<Box<[_]>>>()?;
+            tuple.new_val(values)?
+        }
+        Type::Variant(variant) => {
+            let (name, payload) = match json {
+                Value::String(name) => (name.as_str(), None),
+                json => {
+                    let (name, payload) = case(json)?;
+                    (name, Some(payload))
+                }
+            };
+            let ty = variant
+                .cases()
+                .find(|case| case.name == name)
+                .ok_or_else(|| anyhow!("unknown case `{name}`"))?
+                .ty;
+            let payload = match (ty, payload) {
+                (None, None | Some(Value::Null)) => None,
+                (Some(ty), Some(payload)) => {
+                    Some(from_json(&ty, payload).with_context(|| format!("in case `{name}`"))?)
+                }
+                (Some(_), None) => bail!("case `{name}` requires a payload"),
+                (None, Some(_)) => bail!("case `{name}` has no payload"),
+            };
+            variant.new_val(name, payload)?
+        }
+        Type::Enum(enum_) => enum_.new_val(str(json)?)?,
+        Type::Union(union) => {
+            let value = union
+                .types()
+                .enumerate()
+                .find_map(|(i, ty)| Some((i, from_json(&ty, json).ok()?)));
+            match value {
+                Some((i, value)) => union.new_val(i as u32, value)?,
+                None => bail!("{json} does not match any case of the union"),
+            }
+        }
+        Type::Option(option) => match json {
+            Value::Null => option.new_val(None)?,
+            json => option.new_val(Some(from_json(&option.ty(), json)?))?,
+        },
+        Type::Result(result) => {
+            let (name, payload) = case(json)?;
+            let (ty, ok) = match name {
+                "ok" => (result.ok(), true),
+                "err" => (result.err(), false),
+                _ => bail!("expected `ok` or `err`, found `{name}`"),
+            };
+            let payload = match (ty, payload) {
+                (None, Value::Null) => None,
+                (None, _) => bail!("`{name}` has no payload"),
+                (Some(ty), payload) => {
+                    Some(from_json(&ty, payload).with_context(|| format!("in `{name}`"))?)
+                }
+            };
+            result.new_val(if ok { Ok(payload) } else { Err(payload) })?
+        }
+        Type::Flags(flags) => {
+            let names = array(json)?.iter().map(str).collect::<Result<Vec<_>>>()?;
+            flags.new_val(&names)?
+        }
+    })
+}
+
+/// Convert a value to JSON, in the same form `from_json` accepts.
+pub fn to_json(val: &Val) -> Value {
+    fn float(f: f64) -> Value {
+        // JSON has no representation for NaN and infinities.
+        Number::from_f64(f)
+            .map(Value::Number)
+            .unwrap_or_else(|| Value::String(f.to_string()))
+    }
+
+    fn case(name: &str, payload: Value) -> Value {
+        let mut map = Map::new();
+        map.insert(name.to_owned(), payload);
+        Value::Object(map)
+    }
+
+    match val {
+        Val::Bool(b) => Value::Bool(*b),
+        Val::S8(n) => Value::from(*n),
+        Val::U8(n) => Value::from(*n),
+        Val::S16(n) => Value::from(*n),
+        Val::U16(n) => Value::from(*n),
+        Val::S32(n) => Value::from(*n),
+        Val::U32(n) => Value::from(*n),
+        Val::S64(n) => Value::from(*n),
+        Val::U64(n) => Value::from(*n),
+        Val::Float32(f) => float(f64::from(*f)),
+        Val::Float64(f) => float(*f),
+        Val::Char(c) => Value::String(c.to_string()),
+        Val::String(s) => Value::String(s.to_string()),
+        Val::List(list) => Value::Array(list.iter().map(to_json).collect()),
+        Val::Record(record) => Value::Object(
+            record
+                .fields()
+                .map(|(name, val)| (name.to_owned(), to_json(val)))
+                .collect(),
+        ),
+        Val::Tuple(tuple) => Value::Array(tuple.values().iter().map(to_json).collect()),
+        Val::Variant(variant) => match variant.payload() {
+            Some(payload) => case(variant.discriminant(), to_json(payload)),
+            None => Value::String(variant.discriminant().to_owned()),
+        },
+        Val::Enum(enum_) => Value::String(enum_.discriminant().to_owned()),
+        Val::Union(union) => to_json(union.payload()),
+        Val::Option(option) => option.value().map(to_json).unwrap_or(Value::Null),
+        Val::Result(result) => match result.value() {
+            Ok(payload) => case("ok", payload.map(to_json).unwrap_or(Value::Null)),
+            Err(payload) => case("err", payload.map(to_json).unwrap_or(Value::Null)),
+        },
+        Val::Flags(flags) => Value::Array(
+            flags
+                .flags()
+                .map(|name| Value::String(name.to_owned()))
+                .collect(),
+        ),
+    }
+}
@@ -11,10 +11,16 @@ mod network;
 mod poll;
 mod random;
 mod tcp;
+mod terminal;
 mod udp;
+pub use clocks::track_cpu_time;
 pub use wasi_common::{table::Table, WasiCtx};
 
 type HostResult<T, E> = anyhow::Result<Result<T, E>>;
 
 pub mod command;
+pub mod command_extended;
+pub mod invoke;
+pub mod manifest;
 pub mod proxy;
+pub mod reactor;
@@ -1,12 +1,35 @@
-use anyhow::Result;
-use host::{command, command::wasi::Command, proxy, proxy::wasi::Proxy, WasiCtx};
+use anyhow::{Context, Result};
+use host::{
+    command,
+    command::wasi::Command,
+    command_extended,
+    command_extended::wasi::CommandExtended,
+    invoke,
+    manifest::{Limiter, Limits, Manifest},
+    proxy,
+    proxy::wasi::Proxy,
+    reactor,
+    reactor::wasi::Reactor,
+    track_cpu_time, WasiCtx,
+};
 use wasi_cap_std_sync::WasiCtxBuilder;
+use wasi_common::I32Exit;
 use wasmtime::{
     component::{Component, Linker},
-    Config, Engine, Store,
+    Config, Engine, Store, Trap, WasmBacktrace,
 };
 
 use clap::Parser;
+use std::{fmt, process::ExitCode, time::Duration};
+
+/// Exit status when the guest traps. This matches what a native process
+/// killed by `abort()` reports: `128 + SIGABRT` on Unix, and 3 on Windows.
+const TRAP_EXIT_STATUS: u8 = if cfg!(windows) { 3 } else { 128 + 6 };
+
+/// Exit status when the host itself fails, as opposed to the guest. Like
+/// `env` and `docker run`, use 125 so it doesn't collide with common guest
+/// statuses.
+const HOST_ERROR_EXIT_STATUS: u8 = 125;
 
 /// Simple program to run components with host WASI support.
 #[derive(Parser, Debug)]
@@ -18,82 +41,333 @@ struct Args {
     /// Command-line arguments
     args: Vec<String>,
 
-    /// Name of the world to load it in.
+    /// Name of the world to load it in: `command`, `command-extended`,
+    /// `reactor`, or `proxy`.
     #[arg(long, default_value_t = String::from("command"))]
     world: String,
+
+    /// Call an export of a reactor, given its name and its arguments as a
+    /// JSON array, such as `--invoke add-strings '["hello", "$X"]'`. The
+    /// results are printed as JSON, one per line.
+    #[arg(long, num_args = 2, value_names = ["EXPORT", "ARGS"])]
+    invoke: Option<Vec<String>>,
+
+    /// Filesystem path of a TOML or JSON capability manifest describing the
+    /// WASI context to run the component with.
+    #[arg(long)]
+    manifest: Option<String>,
+
+    /// Maximum size of any linear memory, in bytes. Overrides the manifest.
+    #[arg(long)]
+    max_memory_size: Option<usize>,
+
+    /// Maximum number of elements in any table. Overrides the manifest.
+    #[arg(long)]
+    max_table_elements: Option<u32>,
+
+    /// Amount of fuel the guest may consume, roughly one unit per wasm
+    /// instruction. Overrides the manifest.
+    #[arg(long)]
+    fuel: Option<u64>,
+
+    /// Wall-clock time the guest may run for, in milliseconds. Overrides the
+    /// manifest.
+    #[arg(long)]
+    timeout_ms: Option<u64>,
 }
 
+/// The data stored in the `Store`.
+struct Host {
+    wasi: WasiCtx,
+    limiter: Limiter,
+}
+
+/// A guest failed after running into one of its resource limits.
+#[derive(Debug)]
+struct LimitExceeded(String);
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "guest exceeded {}", self.0)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
 #[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let args = Args::parse();
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => report(e),
+    }
+}
+
+async fn run(args: Args) -> Result<()> {
     let input = args.component;
 
+    if args.invoke.is_some() && args.world != "reactor" {
+        anyhow::bail!("`--invoke` is only supported for the `reactor` world");
+    }
+
+    let manifest = args.manifest.map(Manifest::from_file).transpose()?;
+    let manifest = manifest.as_ref();
+
+    let mut limits = manifest.map(|m| m.limits.clone()).unwrap_or_default();
+    limits.memory_size = args.max_memory_size.or(limits.memory_size);
+    limits.table_elements = args.max_table_elements.or(limits.table_elements);
+    limits.fuel = args.fuel.or(limits.fuel);
+    limits.timeout_ms = args.timeout_ms.or(limits.timeout_ms);
+
     let mut config = Config::new();
     config.wasm_backtrace_details(wasmtime::WasmBacktraceDetails::Enable);
     config.wasm_component_model(true);
     config.async_support(true);
+    // Both of these have a runtime cost, so only enable them when needed.
+    config.consume_fuel(limits.fuel.is_some());
+    config.epoch_interruption(limits.timeout_ms.is_some());
 
     let engine = Engine::new(&config)?;
     let component = Component::from_file(&engine, &input)?;
     let mut linker = Linker::new(&engine);
 
-    if args.world == "command" {
-        run_command(&mut linker, &engine, &component, &args.args).await?;
-    } else if args.world == "proxy" {
-        run_proxy(&mut linker, &engine, &component, &args.args).await?;
+    let world = args.world.as_str();
+    let mut store = new_store(&engine, manifest, &limits, &args.args, |argv| {
+        let builder = WasiCtxBuilder::new().inherit_stdio().args(argv);
+        // The proxy world has no access to sockets.
+        let builder = if world == "proxy" {
+            builder
+        } else {
+            builder.inherit_network()
+        };
+        builder.build()
+    })?;
+
+    let result = match world {
+        "command" => run_command(&mut linker, &mut store, &component).await,
+        "command-extended" => run_command_extended(&mut linker, &mut store, &component).await,
+        "reactor" => run_reactor(&mut linker, &mut store, &component, args.invoke.as_deref()).await,
+        "proxy" => run_proxy(&mut linker, &mut store, &component).await,
+        world => anyhow::bail!("unknown world `{world}`"),
+    };
+
+    check_limits(&store, result)
+}
+
+/// If `result` is a failure caused by a resource limit, say which one.
+fn check_limits<T>(store: &Store<Host>, result: Result<T>) -> Result<T> {
+    let error = match result {
+        Ok(value) => return Ok(value),
+        Err(error) => error,
+    };
+    let limiter = &store.data().limiter;
+    let limits = limiter.limits();
+    let exceeded = match error.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => limits.fuel.map(|fuel| format!("its fuel limit of {fuel}")),
+        Some(Trap::Interrupt) => limits
+            .timeout_ms
+            .map(|ms| format!("its time limit of {ms} ms")),
+        // A guest which is denied memory usually fails in some other way
+        // soon after, such as by aborting.
+        _ if error.downcast_ref::<I32Exit>().is_none() => limiter.exceeded().map(str::to_owned),
+        _ => None,
+    };
+    match exceeded {
+        Some(exceeded) => Err(error.context(LimitExceeded(exceeded))),
+        None => Err(error),
     }
+}
 
-    Ok(())
+/// Print what went wrong, and return the exit status for the host process.
+///
+/// There are four ways a run can end unsuccessfully:
+///  - The guest exited explicitly, through the `exit` interface or by
+///    returning an error from `main`. Nothing is printed, and the guest's
+///    status becomes ours.
+///  - The guest ran into one of its resource limits, and was stopped or
+///    failed as a result. The limit and the underlying failure are printed.
+///  - The guest trapped. The trap and its wasm backtrace are printed, along
+///    with the preview1 function the guest was in, if any.
+///  - The host failed, for example while loading the component or inside a
+///    host function. The error is printed.
+fn report(error: anyhow::Error) -> ExitCode {
+    if let Some(exit) = error.downcast_ref::<I32Exit>() {
+        // On Windows, exit status 3 indicates an abort, so report any status
+        // outside the portable range as a plain failure to avoid ambiguity.
+        if cfg!(windows) && exit.0 >= 3 {
+            return ExitCode::FAILURE;
+        }
+        return match u8::try_from(exit.0) {
+            Ok(status) => ExitCode::from(status),
+            Err(_) => ExitCode::FAILURE,
+        };
+    }
+
+    let backtrace = error.downcast_ref::<WasmBacktrace>();
+    let call = backtrace.and_then(failing_wasi_call);
+
+    if let Some(exceeded) = error.downcast_ref::<LimitExceeded>() {
+        eprintln!("error: {exceeded}");
+        eprintln!("  caused by: {}", error.root_cause());
+        if let Some(call) = call {
+            eprintln!("  while executing WASI call `{call}`");
+        }
+        return ExitCode::from(TRAP_EXIT_STATUS);
+    }
+
+    if let Some(trap) = error.downcast_ref::<Trap>() {
+        eprintln!("error: guest trapped: {trap}");
+        if let Some(call) = call {
+            eprintln!("  while executing WASI call `{call}`");
+        }
+        if let Some(backtrace) = backtrace {
+            eprintln!("{backtrace}");
+        }
+        return ExitCode::from(TRAP_EXIT_STATUS);
+    }
+
+    // The debug representation of the error includes its context, which
+    // contains the backtrace if the error came from a host function.
+    eprintln!("error: {error:?}");
+    if let Some(call) = call {
+        eprintln!("  while executing WASI call `{call}`");
+    }
+    ExitCode::from(HOST_ERROR_EXIT_STATUS)
 }
 
-async fn run_command(
-    linker: &mut Linker<WasiCtx>,
+/// Find the preview1 function the guest was executing, by looking for the
+/// innermost adapter export in the backtrace. The adapter's exports are the
+/// only frames with plain, unmangled preview1 names.
+fn failing_wasi_call(backtrace: &WasmBacktrace) -> Option<&str> {
+    const PREFIXES: &[&str] = &[
+        "args_", "environ_", "clock_", "fd_", "path_", "proc_", "sock_",
+    ];
+    const FUNCTIONS: &[&str] = &["poll_oneoff", "random_get", "sched_yield"];
+
+    backtrace
+        .frames()
+        .iter()
+        .filter_map(|frame| frame.func_name())
+        .find(|name| {
+            FUNCTIONS.contains(name) || PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+        })
+}
+
+/// Create a `Store` holding the context described by `manifest`, or the
+/// context produced by `default` if no manifest was given, and enforcing
+/// `limits`.
+fn new_store(
     engine: &Engine,
-    component: &Component,
+    manifest: Option<&Manifest>,
+    limits: &Limits,
     args: &[String],
+    default: impl FnOnce(&[&str]) -> WasiCtx,
+) -> Result<Store<Host>> {
+    let wasi = match manifest {
+        Some(manifest) => manifest.build_ctx("wasm", args)?,
+        None => {
+            let mut argv: Vec<&str> = vec!["wasm"];
+            argv.extend(args.iter().map(String::as_str));
+            default(&argv)
+        }
+    };
+
+    let limiter = Limiter::new(limits);
+    let mut store = Store::new(engine, Host { wasi, limiter });
+    store.limiter(|host| &mut host.limiter);
+    track_cpu_time(&mut store, |host| &mut host.wasi);
+
+    if let Some(fuel) = limits.fuel {
+        store.add_fuel(fuel)?;
+    }
+    if let Some(ms) = limits.timeout_ms {
+        // Trap at the next epoch, which starts once the timeout elapses.
+        store.set_epoch_deadline(1);
+        let engine = engine.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(ms));
+            engine.increment_epoch();
+        });
+    }
+
+    Ok(store)
+}
+
+async fn run_command(
+    linker: &mut Linker<Host>,
+    store: &mut Store<Host>,
+    component: &Component,
 ) -> anyhow::Result<()> {
-    command::add_to_linker(linker, |x| x)?;
+    command::add_to_linker(linker, |host| &mut host.wasi)?;
+
+    let (wasi, _instance) = Command::instantiate_async(&mut *store, component, linker).await?;
+
+    let result: Result<(), ()> = wasi.call_main(&mut *store).await?;
+
+    // Returning an error from `main` is equivalent to `exit(1)`.
+    if result.is_err() {
+        return Err(I32Exit(1).into());
+    }
 
-    let mut argv: Vec<&str> = vec!["wasm"];
-    argv.extend(args.iter().map(String::as_str));
+    Ok(())
+}
 
-    let mut store = Store::new(
-        engine,
-        WasiCtxBuilder::new()
-            .inherit_stdio()
-            .inherit_network()
-            .args(&argv)
-            .build(),
-    );
+async fn run_command_extended(
+    linker: &mut Linker<Host>,
+    store: &mut Store<Host>,
+    component: &Component,
+) -> anyhow::Result<()> {
+    command_extended::add_to_linker(linker, |host| &mut host.wasi)?;
 
-    let (wasi, _instance) = Command::instantiate_async(&mut store, component, linker).await?;
+    let (wasi, _instance) =
+        CommandExtended::instantiate_async(&mut *store, component, linker).await?;
 
-    let result: Result<(), ()> = wasi.call_main(&mut store).await?;
+    // The arguments are passed to `main` directly, in addition to being
+    // available through the `environment` interface.
+    let argv = store.data().wasi.args.clone();
+    let argv: Vec<&str> = argv.iter().map(String::as_str).collect();
+    let result: Result<(), ()> = wasi.call_main(&mut *store, &argv).await?;
 
+    // Returning an error from `main` is equivalent to `exit(1)`.
     if result.is_err() {
-        anyhow::bail!("command returned with failing exit status");
+        return Err(I32Exit(1).into());
     }
 
     Ok(())
 }
 
-async fn run_proxy(
-    linker: &mut Linker<WasiCtx>,
-    engine: &Engine,
+async fn run_reactor(
+    linker: &mut Linker<Host>,
+    store: &mut Store<Host>,
     component: &Component,
-    args: &[String],
+    invoke: Option<&[String]>,
 ) -> anyhow::Result<()> {
-    proxy::add_to_linker(linker, |x| x)?;
+    reactor::add_to_linker(linker, |host| &mut host.wasi)?;
+
+    // A reactor has no entry point; instantiating it runs its initializers.
+    let (_wasi, instance) = Reactor::instantiate_async(&mut *store, component, linker).await?;
 
-    let mut argv: Vec<&str> = vec!["wasm"];
-    argv.extend(args.iter().map(String::as_str));
+    if let Some([export, args]) = invoke {
+        let func = invoke::find_export(&mut *store, &instance, export)?;
+        let results = invoke::call(&mut *store, &func, args)
+            .await
+            .with_context(|| format!("failed to invoke `{export}`"))?;
+        for result in &results {
+            println!("{}", invoke::to_json(result));
+        }
+    }
 
-    let mut store = Store::new(
-        engine,
-        WasiCtxBuilder::new().inherit_stdio().args(&argv).build(),
-    );
+    Ok(())
+}
+
+async fn run_proxy(
+    linker: &mut Linker<Host>,
+    store: &mut Store<Host>,
+    component: &Component,
+) -> anyhow::Result<()> {
+    proxy::add_to_linker(linker, |host| &mut host.wasi)?;
 
-    let (wasi, _instance) = Proxy::instantiate_async(&mut store, component, linker).await?;
+    let (wasi, _instance) = Proxy::instantiate_async(&mut *store, component, linker).await?;
 
     // TODO: do something
     let _ = wasi;
@@ -0,0 +1,373 @@
+//! Capability manifests for running components.
+//!
+//! A manifest is a TOML or JSON document describing everything a component
+//! is granted when it runs: its arguments and environment, the directories it
+//! may access, the network addresses it may use, where its stdio goes, and the
+//! resource limits of its store. For example:
+//!
+//! ```toml
+//! args = ["--verbose"]
+//!
+//! [env]
+//! RUST_LOG = "debug"
+//!
+//! [[preopens]]
+//! host = "/srv/data"
+//! guest = "/data"
+//! read-only = true
+//!
+//! [[network.allow]]
+//! net = "127.0.0.0/8"
+//! port = 8080
+//!
+//! [stdio]
+//! stdin = "null"
+//! stdout = "inherit"
+//! stderr = { file = "/var/log/component.err", append = true }
+//!
+//! [limits]
+//! memory-size = 67108864
+//! timeout-ms = 30000
+//! ```
+
+use crate::WasiCtx;
+use anyhow::{bail, Context, Result};
+use cap_std::{ambient_authority, fs::Dir};
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use wasi_cap_std_sync::WasiCtxBuilder;
+use wasi_common::{
+    dir::ReadOnlyDir,
+    pipe::{ReadPipe, WritePipe},
+    OutputStream, WasiDir,
+};
+use wasmtime::{ResourceLimiter, StoreLimits, StoreLimitsBuilder};
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+    /// Command-line arguments, not including the program name.
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// Environment variables.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    /// Also pass through the environment of the host process. Variables in
+    /// `env` take precedence.
+    #[serde(default)]
+    pub inherit_env: bool,
+
+    /// Host directories made available to the component.
+    #[serde(default)]
+    pub preopens: Vec<Preopen>,
+
+    #[serde(default)]
+    pub network: Network,
+
+    #[serde(default)]
+    pub stdio: Stdio,
+
+    #[serde(default)]
+    pub limits: Limits,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Preopen {
+    /// Path of the directory on the host.
+    pub host: PathBuf,
+
+    /// Path under which the component sees the directory.
+    pub guest: String,
+
+    /// Deny all operations which would modify the directory.
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Network {
+    /// Address ranges the component may bind or connect to.
+    #[serde(default)]
+    pub allow: Vec<NetworkRule>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct NetworkRule {
+    /// An address range, such as `10.0.0.0/8` or `::1/128`.
+    pub net: IpNet,
+
+    /// Allow only this port. If neither `port` nor `port-range` is given,
+    /// any port is allowed.
+    pub port: Option<u16>,
+
+    /// Allow ports from the first element up to, but not including, the
+    /// second one.
+    pub port_range: Option<(u16, u16)>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Stdio {
+    #[serde(default)]
+    pub stdin: StdioTarget,
+    #[serde(default)]
+    pub stdout: StdioTarget,
+    #[serde(default)]
+    pub stderr: StdioTarget,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum StdioTarget {
+    Kind(StdioKind),
+    File {
+        file: PathBuf,
+        /// For output streams, append to the file instead of truncating it.
+        #[serde(default)]
+        append: bool,
+    },
+}
+
+impl Default for StdioTarget {
+    fn default() -> Self {
+        StdioTarget::Kind(StdioKind::Inherit)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum StdioKind {
+    /// Use the stream of the host process.
+    Inherit,
+    /// Read nothing, and discard anything written.
+    Null,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Limits {
+    /// Maximum size of any linear memory, in bytes.
+    pub memory_size: Option<usize>,
+
+    /// Maximum number of elements in any table.
+    pub table_elements: Option<u32>,
+
+    /// Maximum number of instances in the store.
+    pub instances: Option<usize>,
+
+    /// Amount of fuel the guest may consume. Roughly, one unit of fuel is
+    /// consumed per wasm instruction executed.
+    pub fuel: Option<u64>,
+
+    /// Wall-clock time the guest may run for, in milliseconds.
+    pub timeout_ms: Option<u64>,
+}
+
+impl Manifest {
+    /// Load a manifest from a file. Files ending in `.json` are parsed as
+    /// JSON, everything else as TOML.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Manifest> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read manifest {}", path.display()))?;
+        let manifest = if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+            serde_json::from_str(&contents)?
+        } else {
+            toml::from_str(&contents)?
+        };
+        Ok(manifest)
+    }
+
+    /// Build the `WasiCtx` described by this manifest. `argv0` is used as
+    /// the program name, and `extra_args` are appended after the manifest's
+    /// own arguments.
+    pub fn build_ctx(&self, argv0: &str, extra_args: &[String]) -> Result<WasiCtx> {
+        let mut ctx = WasiCtxBuilder::new().build();
+
+        let mut argv = vec![argv0];
+        argv.extend(self.args.iter().map(String::as_str));
+        argv.extend(extra_args.iter().map(String::as_str));
+        ctx.set_args(&argv);
+
+        if self.inherit_env {
+            for (key, value) in std::env::vars() {
+                if !self.env.contains_key(&key) {
+                    ctx.push_env(&key, &value);
+                }
+            }
+        }
+        for (key, value) in &self.env {
+            ctx.push_env(key, value);
+        }
+
+        for preopen in &self.preopens {
+            let dir = Dir::open_ambient_dir(&preopen.host, ambient_authority())
+                .with_context(|| format!("failed to open directory {}", preopen.host.display()))?;
+            let mut dir: Box<dyn WasiDir> =
+                Box::new(wasi_cap_std_sync::dir::Dir::from_cap_std(dir));
+            if preopen.read_only {
+                dir = Box::new(ReadOnlyDir(dir));
+            }
+            ctx.push_preopened_dir(dir, &preopen.guest)?;
+        }
+
+        for rule in &self.network.allow {
+            match (rule.port, rule.port_range) {
+                (None, None) => ctx.insert_ip_net_port_any(rule.net),
+                (Some(port), None) => ctx.insert_ip_net(rule.net, port),
+                (None, Some((start, end))) => {
+                    ctx.insert_ip_net_port_range(rule.net, start, Some(end))
+                }
+                (Some(_), Some(_)) => {
+                    bail!(
+                        "network rule for {} has both `port` and `port-range`",
+                        rule.net
+                    )
+                }
+            }
+        }
+
+        match &self.stdio.stdin {
+            StdioTarget::Kind(StdioKind::Inherit) => {
+                ctx.set_stdin(Box::new(wasi_cap_std_sync::stdio::stdin()))
+            }
+            StdioTarget::Kind(StdioKind::Null) => {
+                ctx.set_stdin(Box::new(ReadPipe::new(std::io::empty())))
+            }
+            StdioTarget::File { file, append } => {
+                if *append {
+                    bail!("`append` is only meaningful for stdout and stderr");
+                }
+                let file = std::fs::File::open(file)
+                    .with_context(|| format!("failed to open stdin file {}", file.display()))?;
+                ctx.set_stdin(Box::new(ReadPipe::new(file)));
+            }
+        }
+        match output_pipe(&self.stdio.stdout)? {
+            Some(pipe) => ctx.set_stdout(pipe),
+            None => ctx.set_stdout(Box::new(wasi_cap_std_sync::stdio::stdout())),
+        }
+        match output_pipe(&self.stdio.stderr)? {
+            Some(pipe) => ctx.set_stderr(pipe),
+            None => ctx.set_stderr(Box::new(wasi_cap_std_sync::stdio::stderr())),
+        }
+
+        Ok(ctx)
+    }
+}
+
+impl Limits {
+    /// Build a `StoreLimits` enforcing these limits, for use with
+    /// `Store::limiter`.
+    pub fn store_limits(&self) -> StoreLimits {
+        let mut builder = StoreLimitsBuilder::new();
+        if let Some(size) = self.memory_size {
+            builder = builder.memory_size(size);
+        }
+        if let Some(elements) = self.table_elements {
+            builder = builder.table_elements(elements);
+        }
+        if let Some(instances) = self.instances {
+            builder = builder.instances(instances);
+        }
+        builder.build()
+    }
+}
+
+/// A `ResourceLimiter` enforcing the memory and table limits of `Limits`.
+///
+/// Denied growth isn't an error by itself: the guest sees a failed
+/// `memory.grow` or `table.grow`, and may recover. The limiter remembers the
+/// last limit it enforced, so if the guest does fail afterwards, the cause
+/// can be reported.
+pub struct Limiter {
+    limits: Limits,
+    store_limits: StoreLimits,
+    exceeded: Option<String>,
+}
+
+impl Limiter {
+    pub fn new(limits: &Limits) -> Limiter {
+        Limiter {
+            limits: limits.clone(),
+            store_limits: limits.store_limits(),
+            exceeded: None,
+        }
+    }
+
+    /// The limits being enforced.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// A description of the last limit which denied growth, if any.
+    pub fn exceeded(&self) -> Option<&str> {
+        self.exceeded.as_deref()
+    }
+}
+
+impl ResourceLimiter for Limiter {
+    fn memory_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> bool {
+        let allowed = self.store_limits.memory_growing(current, desired, maximum);
+        if !allowed {
+            if let Some(limit) = self.limits.memory_size {
+                self.exceeded = Some(format!(
+                    "the memory limit of {limit} bytes (tried to grow to {desired} bytes)"
+                ));
+            }
+        }
+        allowed
+    }
+
+    fn table_growing(&mut self, current: u32, desired: u32, maximum: Option<u32>) -> bool {
+        let allowed = self.store_limits.table_growing(current, desired, maximum);
+        if !allowed {
+            if let Some(limit) = self.limits.table_elements {
+                self.exceeded = Some(format!(
+                    "the table limit of {limit} elements (tried to grow to {desired} elements)"
+                ));
+            }
+        }
+        allowed
+    }
+
+    fn instances(&self) -> usize {
+        self.store_limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.store_limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.store_limits.memories()
+    }
+}
+
+/// Open the stream an output target writes to, or return `None` if it inherits
+/// the host's stream, which differs between stdout and stderr.
+fn output_pipe(target: &StdioTarget) -> Result<Option<Box<dyn OutputStream>>> {
+    let writer: Box<dyn std::io::Write + Send + Sync> = match target {
+        StdioTarget::Kind(StdioKind::Inherit) => return Ok(None),
+        StdioTarget::Kind(StdioKind::Null) => Box::new(std::io::sink()),
+        StdioTarget::File { file, append } => Box::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(*append)
+                .truncate(!*append)
+                .open(file)
+                .with_context(|| format!("failed to open output file {}", file.display()))?,
+        ),
+    };
+    Ok(Some(Box::new(WritePipe::new(writer))))
+}
@@ -37,7 +37,13 @@ async fn drop_pollable(ctx: &mut WasiCtx, pollable: Pollable) -> anyhow::Result<
     Ok(())
 }
 
-async fn poll_oneoff(ctx: &mut WasiCtx, futures: Vec<Pollable>) -> anyhow::Result<Vec<u8>> {
+/// Poll `futures`, returning for each one `None` if it isn't ready, or the
+/// number of bytes ready and whether it hung up if it is.
+async fn poll(
+    ctx: &mut WasiCtx,
+    futures: Vec<Pollable>,
+) -> anyhow::Result<Vec<Option<(u64, bool)>>> {
+    use wasi_common::sched::subscription::{RwEventFlags, SubscriptionResult};
     use wasi_common::sched::{Poll, Userdata};
 
     // Convert `futures` into `Poll` subscriptions.
@@ -71,14 +77,38 @@ async fn poll_oneoff(ctx: &mut WasiCtx, futures: Vec<Pollable>) -> anyhow::Resul
     // Do the poll.
     ctx.sched.poll_oneoff(&mut poll).await?;
 
-    // Convert the results into a list of `u8` to return.
-    let mut results = vec![0_u8; len];
-    for (_result, data) in poll.results() {
-        results[u64::from(data) as usize] = u8::from(true);
+    // Collect the details of the results. An error is reported as being
+    // ready, so that the error is found when the stream is used.
+    let mut results = vec![None; len];
+    for (result, data) in poll.results() {
+        results[u64::from(data) as usize] = Some(match result {
+            SubscriptionResult::ReadWrite(Ok((nbytes, flags))) => {
+                (nbytes, flags.contains(RwEventFlags::HANGUP))
+            }
+            SubscriptionResult::ReadWrite(Err(_)) | SubscriptionResult::MonotonicClock(_) => {
+                (0, false)
+            }
+        });
     }
     Ok(results)
 }
 
+async fn sched_yield(ctx: &mut WasiCtx) -> anyhow::Result<()> {
+    ctx.sched.sched_yield().await?;
+    // Also let other tasks on the same runtime thread run, which yielding
+    // the thread alone doesn't.
+    tokio::task::yield_now().await;
+    Ok(())
+}
+
+async fn poll_oneoff(ctx: &mut WasiCtx, futures: Vec<Pollable>) -> anyhow::Result<Vec<u8>> {
+    let results = poll(ctx, futures).await?;
+    Ok(results
+        .into_iter()
+        .map(|result| u8::from(result.is_some()))
+        .collect())
+}
+
 // Implementatations of the traits for both the command and proxy worlds.
 // The bodies have been pulled out into functions above to allow them to
 // be shared between the two. Ideally, we should add features to the
@@ -93,6 +123,25 @@ impl command::wasi::poll::Host for WasiCtx {
     async fn poll_oneoff(&mut self, futures: Vec<Pollable>) -> anyhow::Result<Vec<u8>> {
         poll_oneoff(self, futures).await
     }
+
+    async fn poll_oneoff_readiness(
+        &mut self,
+        futures: Vec<Pollable>,
+    ) -> anyhow::Result<Vec<command::wasi::poll::Readiness>> {
+        let results = poll(self, futures).await?;
+        Ok(results
+            .into_iter()
+            .map(|result| command::wasi::poll::Readiness {
+                ready: u8::from(result.is_some()),
+                hangup: u8::from(matches!(result, Some((_, true)))),
+                nbytes: result.map_or(0, |(nbytes, _)| nbytes),
+            })
+            .collect())
+    }
+
+    async fn sched_yield(&mut self) -> anyhow::Result<()> {
+        sched_yield(self).await
+    }
 }
 
 #[async_trait::async_trait]
@@ -104,4 +153,23 @@ impl proxy::wasi::poll::Host for WasiCtx {
     async fn poll_oneoff(&mut self, futures: Vec<Pollable>) -> anyhow::Result<Vec<u8>> {
         poll_oneoff(self, futures).await
     }
+
+    async fn poll_oneoff_readiness(
+        &mut self,
+        futures: Vec<Pollable>,
+    ) -> anyhow::Result<Vec<proxy::wasi::poll::Readiness>> {
+        let results = poll(self, futures).await?;
+        Ok(results
+            .into_iter()
+            .map(|result| proxy::wasi::poll::Readiness {
+                ready: u8::from(result.is_some()),
+                hangup: u8::from(matches!(result, Some((_, true)))),
+                nbytes: result.map_or(0, |(nbytes, _)| nbytes),
+            })
+            .collect())
+    }
+
+    async fn sched_yield(&mut self) -> anyhow::Result<()> {
+        sched_yield(self).await
+    }
 }
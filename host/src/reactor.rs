@@ -0,0 +1,29 @@
+//! The `reactor` world: the `command` world's imports plus the `console` and
+//! `default-outgoing-HTTP` interfaces of the `proxy` world, with no `main`.
+//! Its exports are called directly, see `crate::invoke`.
+
+use crate::WasiCtx;
+
+pub mod wasi {
+    wasmtime::component::bindgen!({
+        path: "../wit",
+        world: "reactor",
+        tracing: true,
+        async: true,
+        trappable_error_type: {
+            "filesystem"::"error-code": Error,
+            "streams"::"stream-error": Error,
+        }
+    });
+}
+
+pub fn add_to_linker<T: Send>(
+    l: &mut wasmtime::component::Linker<T>,
+    f: impl (Fn(&mut T) -> &mut WasiCtx) + Copy + Send + Sync + 'static,
+) -> anyhow::Result<()> {
+    crate::command::add_to_linker(l, f)?;
+    crate::proxy::wasi::console::add_to_linker(l, f)?;
+    crate::proxy::wasi::types::add_to_linker(l, f)?;
+    crate::proxy::wasi::default_outgoing_http::add_to_linker(l, f)?;
+    Ok(())
+}
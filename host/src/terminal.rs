@@ -0,0 +1,30 @@
+use crate::command::wasi::{
+    streams::{InputStream, OutputStream},
+    terminal_input, terminal_output,
+};
+use crate::WasiCtx;
+use wasi_common::stream::TableStreamExt;
+
+#[async_trait::async_trait]
+impl terminal_input::Host for WasiCtx {
+    async fn is_terminal(&mut self, this: InputStream) -> anyhow::Result<bool> {
+        Ok(self.table().get_input_stream(this)?.isatty())
+    }
+}
+
+#[async_trait::async_trait]
+impl terminal_output::Host for WasiCtx {
+    async fn is_terminal(&mut self, this: OutputStream) -> anyhow::Result<bool> {
+        Ok(self.table().get_output_stream(this)?.isatty())
+    }
+
+    async fn get_window_size(
+        &mut self,
+        this: OutputStream,
+    ) -> anyhow::Result<Option<terminal_output::WindowSize>> {
+        let stream = self.table().get_output_stream(this)?;
+        Ok(stream
+            .window_size()
+            .map(|(columns, rows)| terminal_output::WindowSize { columns, rows }))
+    }
+}
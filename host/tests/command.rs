@@ -10,7 +10,7 @@ use wasi_cap_std_sync::WasiCtxBuilder;
 use wasi_common::{
     clocks::{WasiMonotonicClock, WasiWallClock},
     dir::ReadOnlyDir,
-    pipe::ReadPipe,
+    pipe::{ReadPipe, WritePipe},
 };
 use wasmtime::{
     component::{Component, Linker},
@@ -50,6 +50,7 @@ async fn instantiate(path: &str) -> Result<(Store<WasiCtx>, Command)> {
             .inherit_stderr()
             .build(),
     );
+    host::track_cpu_time(&mut store, |wasi| wasi);
 
     let (wasi, _instance) = Command::instantiate_async(&mut store, &component, &linker).await?;
     Ok((store, wasi))
@@ -178,6 +179,22 @@ async fn run_poll_stdin(mut store: Store<WasiCtx>, wasi: Command) -> Result<()>
         .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
 }
 
+async fn run_stdio_terminal(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    store
+        .data_mut()
+        .set_stdin(Box::new(ReadPipe::new(Cursor::new(""))));
+    store
+        .data_mut()
+        .set_stdout(Box::new(WritePipe::new(std::io::sink())));
+    store
+        .data_mut()
+        .set_stderr(Box::new(WritePipe::new(std::io::sink())));
+
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+}
+
 async fn run_env(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
     store.data_mut().push_env("frabjous", "day");
     store.data_mut().push_env("callooh", "callay");
@@ -186,6 +203,18 @@ async fn run_env(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
         .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
 }
 
+async fn run_large_env(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    for i in 0..1000 {
+        store
+            .data_mut()
+            .push_env(&format!("VAR{i}"), &i.to_string().repeat(100));
+    }
+    store.data_mut().push_env("BIG", &"x".repeat(200_000));
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+}
+
 async fn run_file_read(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
     let dir = tempfile::tempdir()?;
 
@@ -202,6 +231,59 @@ async fn run_file_read(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
         .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
 }
 
+async fn run_file_rights(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::File::create(dir.path().join("bar.txt"))?;
+
+    let open_dir = Dir::open_ambient_dir(dir.path(), ambient_authority())?;
+    store.data_mut().push_preopened_dir(
+        Box::new(wasi_cap_std_sync::dir::Dir::from_cap_std(open_dir)),
+        "/",
+    )?;
+
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))?;
+
+    let contents = std::fs::read(dir.path().join("bar.txt"))?;
+    assert_eq!(std::str::from_utf8(&contents).unwrap(), "before\n");
+    Ok(())
+}
+
+async fn run_many_files(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    let open_dir = Dir::open_ambient_dir(dir.path(), ambient_authority())?;
+    store.data_mut().push_preopened_dir(
+        Box::new(wasi_cap_std_sync::dir::Dir::from_cap_std(open_dir)),
+        "/",
+    )?;
+
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))?;
+
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("file299.txt"))?,
+        "299"
+    );
+    Ok(())
+}
+
+async fn run_readlink_overflow(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    let open_dir = Dir::open_ambient_dir(dir.path(), ambient_authority())?;
+    store.data_mut().push_preopened_dir(
+        Box::new(wasi_cap_std_sync::dir::Dir::from_cap_std(open_dir)),
+        "/",
+    )?;
+
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+}
+
 async fn run_file_append(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
     let dir = tempfile::tempdir()?;
 
@@ -268,6 +350,40 @@ async fn run_exit_failure(mut store: Store<WasiCtx>, wasi: Command) -> Result<()
     Ok(())
 }
 
+async fn run_exit_code(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    let r = wasi.call_main(&mut store).await;
+    let err = r.unwrap_err();
+    let status = err.downcast_ref::<wasi_common::I32Exit>().unwrap();
+    assert_eq!(status.0, 42);
+    Ok(())
+}
+
+async fn run_raise_abort(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    let r = wasi.call_main(&mut store).await;
+    let err = r.unwrap_err();
+    let status = err.downcast_ref::<wasi_common::I32Exit>().unwrap();
+    assert_eq!(status.0, 128 + 6);
+    Ok(())
+}
+
+async fn run_poll_realtime(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+}
+
+async fn run_cpu_time(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+}
+
+async fn run_unknown_clock(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+}
+
 async fn run_exit_panic(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
     let r = wasi.call_main(&mut store).await;
     let err = r.unwrap_err();
@@ -297,6 +413,88 @@ async fn run_directory_list(mut store: Store<WasiCtx>, wasi: Command) -> Result<
         .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
 }
 
+async fn run_relative_paths(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    std::fs::write(dir.path().join("top.txt"), "and the slithy toves")?;
+    std::fs::create_dir(dir.path().join("sub"))?;
+    std::fs::write(dir.path().join("sub").join("data.txt"), "Twas brillig")?;
+
+    let open_dir = Dir::open_ambient_dir(dir.path(), ambient_authority())?;
+    store.data_mut().push_preopened_dir(
+        Box::new(wasi_cap_std_sync::dir::Dir::from_cap_std(open_dir)),
+        "/work",
+    )?;
+    store.data_mut().push_env("PWD", "/work/sub");
+
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))?;
+
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("sub").join("new.txt"))?,
+        "Did gyre and gimble"
+    );
+    Ok(())
+}
+
+async fn run_long_names(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    for i in 0..3 {
+        std::fs::File::create(dir.path().join(format!("{i}{}", "x".repeat(254))))?;
+    }
+    std::fs::File::create(dir.path().join("short.txt"))?;
+
+    let open_dir = Dir::open_ambient_dir(dir.path(), ambient_authority())?;
+    store.data_mut().push_preopened_dir(
+        Box::new(wasi_cap_std_sync::dir::Dir::from_cap_std(open_dir)),
+        "/",
+    )?;
+
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+}
+
+async fn run_sockets(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
+    use std::io::Read;
+    use std::net::{Shutdown, TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    store.data_mut().insert_listener(
+        3,
+        Box::new(wasi_cap_std_sync::net::TcpSocket::sock(listener.into())),
+    );
+
+    // Each client sends a greeting and then reads whatever reply it gets.
+    let clients = (0..2)
+        .map(|_| {
+            std::thread::spawn(move || -> std::io::Result<Vec<u8>> {
+                let mut stream = TcpStream::connect(addr)?;
+                stream.write_all(b"hello")?;
+                stream.shutdown(Shutdown::Write)?;
+                let mut reply = Vec::new();
+                stream.read_to_end(&mut reply)?;
+                Ok(reply)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    wasi.call_main(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))?;
+
+    let mut replies = clients
+        .into_iter()
+        .map(|client| client.join().unwrap())
+        .collect::<std::io::Result<Vec<_>>>()?;
+    replies.sort();
+    assert_eq!(replies, [b"".to_vec(), b"world".to_vec()]);
+    Ok(())
+}
+
 async fn run_default_clocks(mut store: Store<WasiCtx>, wasi: Command) -> Result<()> {
     wasi.call_main(&mut store)
         .await?
@@ -377,7 +575,7 @@ async fn run_fd_readdir(store: Store<WasiCtx>, wasi: Command) -> Result<()> {
 }
 
 async fn run_file_allocate(store: Store<WasiCtx>, wasi: Command) -> Result<()> {
-    expect_fail(run_with_temp_dir(store, wasi).await)
+    run_with_temp_dir(store, wasi).await
 }
 
 async fn run_file_pread_pwrite(store: Store<WasiCtx>, wasi: Command) -> Result<()> {
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use host::invoke::{from_json, to_json};
+use serde_json::json;
+use wasmtime::component::{Type, Val};
+
+#[test]
+fn primitives_round_trip() -> Result<()> {
+    let cases = [
+        (Type::Bool, json!(true)),
+        (Type::S8, json!(-128)),
+        (Type::U16, json!(65535)),
+        (Type::S64, json!(-1)),
+        (Type::U64, json!(u64::MAX)),
+        (Type::Float64, json!(0.5)),
+        (Type::Char, json!("🚩")),
+        (Type::String, json!("$GOOD_DOG")),
+    ];
+    for (ty, json) in cases {
+        let val = from_json(&ty, &json)?;
+        assert_eq!(val.ty(), ty);
+        assert_eq!(to_json(&val), json);
+    }
+    Ok(())
+}
+
+#[test]
+fn mismatched_primitives_are_rejected() {
+    assert!(from_json(&Type::U8, &json!(256)).is_err());
+    assert!(from_json(&Type::U32, &json!(-1)).is_err());
+    assert!(from_json(&Type::S32, &json!(1.5)).is_err());
+    assert!(from_json(&Type::Char, &json!("ab")).is_err());
+    assert!(from_json(&Type::String, &json!(42)).is_err());
+    assert!(from_json(&Type::Bool, &json!(null)).is_err());
+}
+
+#[test]
+fn integers_keep_their_type() -> Result<()> {
+    assert!(matches!(from_json(&Type::U32, &json!(42))?, Val::U32(42)));
+    assert!(matches!(from_json(&Type::S16, &json!(-7))?, Val::S16(-7)));
+    Ok(())
+}
+
+/// Get the parameter types of a component function, since compound `Type`s
+/// can only come from a component. `core_params` is the number of flattened
+/// core parameters `params` lower to.
+fn param_types(params: &str, core_params: usize) -> Result<Vec<Type>> {
+    let wat = format!(
+        r#"
+        (component
+          (core module $m
+            (memory (export "memory") 1)
+            (func (export "f") (param {}))
+            (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+              i32.const 0))
+          (core instance $i (instantiate $m))
+          (func (export "f") {params}
+            (canon lift (core func $i "f") (memory $i "memory")
+              (realloc (func $i "realloc")))))
+        "#,
+        "i32 ".repeat(core_params),
+    );
+    let mut config = wasmtime::Config::new();
+    config.wasm_component_model(true);
+    let engine = wasmtime::Engine::new(&config)?;
+    let component = wasmtime::component::Component::new(&engine, wat)?;
+    let mut store = wasmtime::Store::new(&engine, ());
+    let instance = wasmtime::component::Linker::new(&engine).instantiate(&mut store, &component)?;
+    let func = instance.get_func(&mut store, "f").expect("`f` is exported");
+    Ok(func.params(&store).into_vec())
+}
+
+#[test]
+fn compound_types_round_trip() -> Result<()> {
+    let types = param_types(
+        r#"
+        (param "record" (record (field "a" u32) (field "b" string)))
+        (param "variant" (variant (case "none") (case "some" u8)))
+        (param "enum" (enum "red" "green"))
+        (param "option" (option string))
+        (param "result" (result u32 (error string)))
+        (param "list" (list (record (field "a" u32) (field "b" string))))
+        "#,
+        14,
+    )?;
+    let cases = [
+        vec![json!({"a": 1, "b": "one"})],
+        vec![json!("none"), json!({"some": 7})],
+        vec![json!("green")],
+        vec![json!(null), json!("there")],
+        vec![json!({"ok": 42}), json!({"err": "oops"})],
+        vec![json!([]), json!([{"a": 1, "b": "x"}, {"a": 2, "b": "y"}])],
+    ];
+    for (ty, cases) in types.iter().zip(cases) {
+        for json in cases {
+            let val = from_json(ty, &json)?;
+            assert_eq!(&val.ty(), ty);
+            assert_eq!(to_json(&val), json);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn mismatched_compound_types_are_rejected() -> Result<()> {
+    let types = param_types(
+        r#"
+        (param "record" (record (field "a" u32)))
+        (param "variant" (variant (case "none") (case "some" u8)))
+        (param "enum" (enum "red" "green"))
+        (param "result" (result u32))
+        (param "list" (list u8))
+        "#,
+        8,
+    )?;
+    let [record, variant, enum_, result, list] = &types[..] else {
+        panic!("expected five parameters, found {}", types.len());
+    };
+
+    assert!(from_json(record, &json!({"a": "one"})).is_err());
+    assert!(from_json(record, &json!({})).is_err());
+    assert!(from_json(record, &json!({"a": 1, "b": 2})).is_err());
+    assert!(from_json(variant, &json!("some")).is_err());
+    assert!(from_json(variant, &json!({"none": 1})).is_err());
+    assert!(from_json(variant, &json!("maybe")).is_err());
+    assert!(from_json(enum_, &json!("blue")).is_err());
+    assert!(from_json(result, &json!({"ok": 1, "err": null})).is_err());
+    assert!(from_json(result, &json!({"err": 1})).is_err());
+    assert!(from_json(list, &json!([1, 256])).is_err());
+    assert!(from_json(list, &json!({"a": 1})).is_err());
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use anyhow::Result;
+use host::manifest::{Limiter, Limits, Manifest, StdioKind, StdioTarget};
+use std::io::Write;
+use wasmtime::ResourceLimiter;
+
+#[test]
+fn toml_manifest() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("manifest.toml");
+    write!(
+        std::fs::File::create(&path)?,
+        r#"
+            args = ["--verbose"]
+
+            [env]
+            GOOD_DOG = "gussie"
+
+            [[preopens]]
+            host = {host:?}
+            guest = "/data"
+            read-only = true
+
+            [[network.allow]]
+            net = "127.0.0.0/8"
+            port = 8080
+
+            [stdio]
+            stdin = "null"
+            stderr = {{ file = {stderr:?}, append = true }}
+
+            [limits]
+            memory-size = 65536
+            fuel = 1000000
+            timeout-ms = 500
+        "#,
+        host = dir.path(),
+        stderr = dir.path().join("stderr.txt"),
+    )?;
+
+    let manifest = Manifest::from_file(&path)?;
+    assert_eq!(manifest.limits.memory_size, Some(65536));
+    assert_eq!(manifest.limits.fuel, Some(1000000));
+    assert_eq!(manifest.limits.timeout_ms, Some(500));
+    assert!(matches!(
+        manifest.stdio.stdin,
+        StdioTarget::Kind(StdioKind::Null)
+    ));
+    assert!(matches!(
+        manifest.stdio.stdout,
+        StdioTarget::Kind(StdioKind::Inherit)
+    ));
+    assert!(matches!(
+        manifest.stdio.stderr,
+        StdioTarget::File { append: true, .. }
+    ));
+
+    let ctx = manifest.build_ctx("wasm", &["extra".to_owned()])?;
+    assert_eq!(ctx.args, &["wasm", "--verbose", "extra"]);
+    assert_eq!(ctx.env, &[("GOOD_DOG".to_owned(), "gussie".to_owned())]);
+    assert_eq!(ctx.preopens.len(), 1);
+    assert_eq!(ctx.preopens[0].1, "/data");
+    Ok(())
+}
+
+#[test]
+fn json_manifest() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("manifest.json");
+    std::fs::write(
+        &path,
+        r#"{
+            "args": ["a", "b"],
+            "network": { "allow": [{ "net": "::1/128", "port-range": [8000, 9000] }] }
+        }"#,
+    )?;
+
+    let manifest = Manifest::from_file(&path)?;
+    let ctx = manifest.build_ctx("wasm", &[])?;
+    assert_eq!(ctx.args, &["wasm", "a", "b"]);
+    assert!(ctx.env.is_empty());
+    Ok(())
+}
+
+#[test]
+fn unknown_fields_are_rejected() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("manifest.toml");
+    std::fs::write(&path, "argz = [\"typo\"]\n")?;
+
+    assert!(Manifest::from_file(&path).is_err());
+    Ok(())
+}
+
+#[test]
+fn conflicting_port_rules_are_rejected() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("manifest.toml");
+    std::fs::write(
+        &path,
+        "[[network.allow]]\nnet = \"10.0.0.0/8\"\nport = 80\nport-range = [80, 90]\n",
+    )?;
+
+    let manifest = Manifest::from_file(&path)?;
+    assert!(manifest.build_ctx("wasm", &[]).is_err());
+    Ok(())
+}
+
+#[test]
+fn limiter_remembers_denied_growth() {
+    let mut limiter = Limiter::new(&Limits {
+        memory_size: Some(65536),
+        ..Limits::default()
+    });
+
+    assert!(limiter.memory_growing(0, 65536, None));
+    assert!(limiter.exceeded().is_none());
+
+    assert!(!limiter.memory_growing(65536, 131072, None));
+    let exceeded = limiter.exceeded().unwrap();
+    assert!(exceeded.contains("65536"), "{exceeded}");
+}
@@ -1,11 +1,49 @@
 use crate::bindings::streams::{self, InputStream, OutputStream};
-use crate::bindings::{filesystem, tcp};
-use crate::{set_stderr_stream, BumpArena, File, ImportAlloc, TrappingUnwrap, WasmStr};
+#[cfg(sockets)]
+use crate::bindings::tcp;
+use crate::bindings::{filesystem, terminal_input, terminal_output};
+use crate::{set_stderr_stream, BumpArena, ImportAlloc, TrappingUnwrap};
+#[cfg(filesystem)]
+use crate::{File, WasmStr};
 use core::cell::{Cell, UnsafeCell};
 use core::mem::MaybeUninit;
-use wasi::{Errno, Fd};
-
-pub const MAX_DESCRIPTORS: usize = 128;
+use wasi::{Errno, Fd, Filetype, Rights};
+
+/// The number of descriptors stored inline in `State`. Descriptors beyond
+/// these are stored in an overflow table allocated from the main module.
+pub const INLINE_DESCRIPTORS: usize = 128;
+
+/// The maximum number of descriptors, after which opening more fails with
+/// `ERRNO_NOMEM`. This bounds the overflow table, which `fd_renumber` can
+/// otherwise grow arbitrarily.
+pub const MAX_DESCRIPTORS: usize = 1 << 16;
+
+/// The number of descriptors in each chunk of the overflow table. A chunk is
+/// a page, as that's all the main module's `cabi_realloc` can be relied upon
+/// to allocate; see `alloc_pages`.
+const OVERFLOW_CHUNK_LEN: usize = crate::PAGE_SIZE / core::mem::size_of::<Descriptor>();
+
+/// The number of chunks needed to hold `MAX_DESCRIPTORS`.
+const OVERFLOW_CHUNKS: usize = (MAX_DESCRIPTORS - INLINE_DESCRIPTORS).div_ceil(OVERFLOW_CHUNK_LEN);
+
+/// All of the preview1 rights, which is what preopened directories start out
+/// with. Descriptors opened from them get their inheriting rights.
+#[cfg(filesystem)]
+pub const RIGHTS_ALL: Rights = (wasi::RIGHTS_SOCK_ACCEPT << 1) - 1;
+
+/// The rights which apply to stdio and other streams. These leave out seek
+/// and tell, which wasi-libc's `isatty` relies on for terminals.
+pub const RIGHTS_STREAM: Rights = wasi::RIGHTS_FD_READ
+    | wasi::RIGHTS_FD_WRITE
+    | wasi::RIGHTS_FD_FDSTAT_SET_FLAGS
+    | wasi::RIGHTS_FD_FILESTAT_GET
+    | wasi::RIGHTS_POLL_FD_READWRITE;
+
+/// The rights which apply to sockets: those of streams plus the `sock_*`
+/// functions.
+#[cfg(sockets)]
+pub const RIGHTS_SOCKET: Rights =
+    RIGHTS_STREAM | wasi::RIGHTS_SOCK_SHUTDOWN | wasi::RIGHTS_SOCK_ACCEPT;
 
 #[repr(C)]
 pub enum Descriptor {
@@ -28,9 +66,11 @@ impl Drop for Descriptor {
                     streams::drop_output_stream(output);
                 }
                 match &stream.type_ {
+                    #[cfg(filesystem)]
                     StreamType::File(file) => filesystem::drop_descriptor(file.fd),
-                    StreamType::Socket(_) => unreachable!(),
-                    StreamType::Unknown => {}
+                    #[cfg(sockets)]
+                    StreamType::Socket(socket) => tcp::drop_tcp_socket(*socket),
+                    StreamType::Stdio(_) => {}
                 }
             }
             Descriptor::Closed(_) => {}
@@ -50,6 +90,13 @@ pub struct Streams {
 
     /// Information about the source of the stream.
     pub type_: StreamType,
+
+    /// The preview1 rights of this descriptor. Preview2 has no equivalent,
+    /// so the adapter tracks and checks them itself.
+    pub rights_base: Cell<Rights>,
+
+    /// The preview1 rights of descriptors opened from this one.
+    pub rights_inheriting: Cell<Rights>,
 }
 
 impl Streams {
@@ -60,6 +107,7 @@ impl Streams {
             None => match &self.type_ {
                 // For files, we may have adjusted the position for seeking, so
                 // create a new stream.
+                #[cfg(filesystem)]
                 StreamType::File(file) => {
                     let input = filesystem::read_via_stream(file.fd, file.position.get());
                     self.input.set(Some(input));
@@ -77,6 +125,7 @@ impl Streams {
             None => match &self.type_ {
                 // For files, we may have adjusted the position for seeking, so
                 // create a new stream.
+                #[cfg(filesystem)]
                 StreamType::File(file) => {
                     let output = if file.append {
                         filesystem::append_via_stream(file.fd)
@@ -92,131 +141,237 @@ impl Streams {
     }
 }
 
-#[allow(dead_code)] // until Socket is implemented
 pub enum StreamType {
-    /// It's a valid stream but we don't know where it comes from.
-    Unknown,
+    /// A stdio stream, with the type of what it's connected to.
+    Stdio(Filetype),
 
     /// Streaming data with a file.
+    #[cfg(filesystem)]
     File(File),
 
     /// Streaming data with a socket connection.
+    #[cfg(sockets)]
     Socket(tcp::TcpSocket),
 }
 
+/// Terminals are reported as character devices, as on POSIX hosts.
+fn stdio_filetype(tty: bool, type_: filesystem::DescriptorType) -> Filetype {
+    if tty {
+        wasi::FILETYPE_CHARACTER_DEVICE
+    } else {
+        type_.into()
+    }
+}
+
 #[repr(C)]
 pub struct Descriptors {
     /// Storage of mapping from preview1 file descriptors to preview2 file
-    /// descriptors.
-    table: UnsafeCell<MaybeUninit<[Descriptor; MAX_DESCRIPTORS]>>,
-    table_len: Cell<u16>,
+    /// descriptors, for the first `INLINE_DESCRIPTORS` descriptors.
+    table: UnsafeCell<MaybeUninit<[Descriptor; INLINE_DESCRIPTORS]>>,
+    table_len: Cell<u32>,
+
+    /// Storage for the descriptors following the inline ones, in page-sized
+    /// chunks of `OVERFLOW_CHUNK_LEN` descriptors which are allocated as the
+    /// table reaches them. Null until then.
+    overflow: [*mut Descriptor; OVERFLOW_CHUNKS],
 
     /// Points to the head of a free-list of closed file descriptors.
     closed: Option<Fd>,
 
     /// Preopened directories. Initialized lazily. Access with `State::get_preopens`
     /// to take care of initialization.
+    #[cfg(filesystem)]
     preopens: Cell<Option<&'static [Preopen]>>,
 }
 
 impl Descriptors {
-    pub fn new(import_alloc: &ImportAlloc, arena: &BumpArena) -> Self {
-        let d = Descriptors {
+    /// Create the descriptor table, with stdio and the preopens. If `pwd` names
+    /// a directory within a preopen, it's added as another preopen named `.`.
+    pub fn new(import_alloc: &ImportAlloc, arena: &BumpArena, pwd: Option<&[u8]>) -> Self {
+        let mut d = Descriptors {
             table: UnsafeCell::new(MaybeUninit::uninit()),
             table_len: Cell::new(0),
+            overflow: [std::ptr::null_mut(); OVERFLOW_CHUNKS],
             closed: None,
+            #[cfg(filesystem)]
             preopens: Cell::new(None),
         };
 
         let stdio = crate::bindings::preopens::get_stdio();
         unsafe { set_stderr_stream(stdio.stderr) };
+        let types = crate::bindings::preopens::get_stdio_types();
+        let stdin_tty = terminal_input::is_terminal(stdio.stdin);
+        let stdout_tty = terminal_output::is_terminal(stdio.stdout);
+        let stderr_tty = terminal_output::is_terminal(stdio.stderr);
 
         d.push(Descriptor::Streams(Streams {
             input: Cell::new(Some(stdio.stdin)),
             output: Cell::new(None),
-            type_: StreamType::Unknown,
+            type_: StreamType::Stdio(stdio_filetype(stdin_tty, types.stdin)),
+            rights_base: Cell::new(RIGHTS_STREAM),
+            rights_inheriting: Cell::new(RIGHTS_STREAM),
         }))
         .trapping_unwrap();
         d.push(Descriptor::Streams(Streams {
             input: Cell::new(None),
             output: Cell::new(Some(stdio.stdout)),
-            type_: StreamType::Unknown,
+            type_: StreamType::Stdio(stdio_filetype(stdout_tty, types.stdout)),
+            rights_base: Cell::new(RIGHTS_STREAM),
+            rights_inheriting: Cell::new(RIGHTS_STREAM),
         }))
         .trapping_unwrap();
         d.push(Descriptor::Streams(Streams {
             input: Cell::new(None),
             output: Cell::new(Some(stdio.stderr)),
-            type_: StreamType::Unknown,
+            type_: StreamType::Stdio(stdio_filetype(stderr_tty, types.stderr)),
+            rights_base: Cell::new(RIGHTS_STREAM),
+            rights_inheriting: Cell::new(RIGHTS_STREAM),
         }))
         .trapping_unwrap();
 
-        #[link(wasm_import_module = "preopens")]
-        extern "C" {
-            #[link_name = "get-directories"]
-            fn get_preopens_import(rval: *mut PreopenList);
+        #[cfg(filesystem)]
+        {
+            #[link(wasm_import_module = "preopens")]
+            extern "C" {
+                #[link_name = "get-directories"]
+                fn get_preopens_import(rval: *mut PreopenList);
+            }
+            let mut list = PreopenList {
+                base: std::ptr::null(),
+                len: 0,
+            };
+            import_alloc.with_arena(arena, || unsafe {
+                get_preopens_import(&mut list as *mut _)
+            });
+            let mut preopens: &'static [Preopen] = unsafe {
+                // allocation comes from long lived arena, so it is safe to
+                // cast this to a &'static slice:
+                std::slice::from_raw_parts(list.base, list.len)
+            };
+            if let Some(cwd) = pwd.and_then(|pwd| open_cwd(preopens, pwd)) {
+                preopens = add_cwd_preopen(preopens, cwd, arena);
+            }
+            for preopen in preopens {
+                // Expectation is that the descriptor index is initialized with
+                // stdio (0,1,2) and no others, so that preopens are 3..
+                d.push(Descriptor::Streams(Streams {
+                    input: Cell::new(None),
+                    output: Cell::new(None),
+                    type_: StreamType::File(File {
+                        fd: preopen.descriptor,
+                        position: Cell::new(0),
+                        append: false,
+                    }),
+                    rights_base: Cell::new(RIGHTS_ALL),
+                    rights_inheriting: Cell::new(RIGHTS_ALL),
+                }))
+                .trapping_unwrap();
+            }
+
+            d.preopens.set(Some(preopens));
         }
-        let mut list = PreopenList {
-            base: std::ptr::null(),
-            len: 0,
-        };
-        import_alloc.with_arena(arena, || unsafe {
-            get_preopens_import(&mut list as *mut _)
-        });
-        let preopens: &'static [Preopen] = unsafe {
-            // allocation comes from long lived arena, so it is safe to
-            // cast this to a &'static slice:
-            std::slice::from_raw_parts(list.base, list.len)
-        };
-        for preopen in preopens {
-            // Expectation is that the descriptor index is initialized with
-            // stdio (0,1,2) and no others, so that preopens are 3..
-            d.push(Descriptor::Streams(Streams {
-                input: Cell::new(None),
-                output: Cell::new(None),
-                type_: StreamType::File(File {
-                    fd: preopen.descriptor,
-                    position: Cell::new(0),
-                    append: false,
-                }),
-            }))
-            .trapping_unwrap();
+
+        #[cfg(sockets)]
+        {
+            #[link(wasm_import_module = "preopens")]
+            extern "C" {
+                #[link_name = "get-sockets"]
+                fn get_sockets_import(rval: *mut SocketList);
+            }
+            let mut list = SocketList {
+                base: std::ptr::null(),
+                len: 0,
+            };
+            import_alloc.with_arena(arena, || unsafe { get_sockets_import(&mut list as *mut _) });
+            let sockets: &[tcp::TcpSocket] =
+                unsafe { std::slice::from_raw_parts(list.base, list.len) };
+            for socket in sockets {
+                // Preopened sockets follow the preopened directories, so that
+                // `fd_prestat_get` failing on the first of them ends the scan for
+                // preopened directories.
+                d.push(Descriptor::Streams(Streams {
+                    input: Cell::new(None),
+                    output: Cell::new(None),
+                    type_: StreamType::Socket(*socket),
+                    rights_base: Cell::new(RIGHTS_SOCKET),
+                    rights_inheriting: Cell::new(RIGHTS_SOCKET),
+                }))
+                .trapping_unwrap();
+            }
         }
 
-        d.preopens.set(Some(preopens));
         d
     }
 
-    fn push(&self, desc: Descriptor) -> Result<Fd, Errno> {
+    fn push(&mut self, desc: Descriptor) -> Result<Fd, Errno> {
+        let len = usize::try_from(self.table_len.get()).trapping_unwrap();
+        if len >= MAX_DESCRIPTORS {
+            return Err(wasi::ERRNO_NOMEM);
+        }
         unsafe {
-            let table = (*self.table.get()).as_mut_ptr();
-            let len = usize::try_from(self.table_len.get()).trapping_unwrap();
-            if len >= (*table).len() {
-                return Err(wasi::ERRNO_NOMEM);
+            if len < INLINE_DESCRIPTORS {
+                let table = (*self.table.get()).as_mut_ptr();
+                core::ptr::addr_of_mut!((*table)[len]).write(desc);
+            } else {
+                let index = len - INLINE_DESCRIPTORS;
+                let chunk = index / OVERFLOW_CHUNK_LEN;
+                if self.overflow[chunk].is_null() {
+                    self.alloc_overflow_chunk(chunk)?;
+                }
+                self.overflow[chunk]
+                    .add(index % OVERFLOW_CHUNK_LEN)
+                    .write(desc);
             }
-            core::ptr::addr_of_mut!((*table)[len]).write(desc);
-            self.table_len.set(u16::try_from(len + 1).trapping_unwrap());
-            Ok(Fd::from(u32::try_from(len).trapping_unwrap()))
         }
+        self.table_len.set(u32::try_from(len + 1).trapping_unwrap());
+        Ok(Fd::from(u32::try_from(len).trapping_unwrap()))
     }
 
-    fn table(&self) -> &[Descriptor] {
-        unsafe {
-            std::slice::from_raw_parts(
-                (*self.table.get()).as_ptr().cast(),
-                usize::try_from(self.table_len.get()).trapping_unwrap(),
-            )
+    /// Allocate another chunk of the overflow table. Chunks never move once
+    /// allocated, so this doesn't disturb the descriptors in earlier ones.
+    #[cold]
+    fn alloc_overflow_chunk(&mut self, chunk: usize) -> Result<(), Errno> {
+        let ptr = crate::alloc_pages(core::mem::align_of::<Descriptor>(), crate::PAGE_SIZE);
+        if ptr.is_null() {
+            return Err(wasi::ERRNO_NOMEM);
         }
+        self.overflow[chunk] = ptr.cast();
+        Ok(())
+    }
+
+    /// The number of descriptors in the inline table and the overflow
+    /// table, respectively.
+    fn lens(&self) -> (usize, usize) {
+        let len = usize::try_from(self.table_len.get()).trapping_unwrap();
+        if len <= INLINE_DESCRIPTORS {
+            (len, 0)
+        } else {
+            (INLINE_DESCRIPTORS, len - INLINE_DESCRIPTORS)
+        }
+    }
+
+    fn table(&self) -> &[Descriptor] {
+        let (len, _) = self.lens();
+        unsafe { std::slice::from_raw_parts((*self.table.get()).as_ptr().cast(), len) }
     }
 
     fn table_mut(&mut self) -> &mut [Descriptor] {
-        unsafe {
-            std::slice::from_raw_parts_mut(
-                (*self.table.get()).as_mut_ptr().cast(),
-                usize::try_from(self.table_len.get()).trapping_unwrap(),
-            )
+        let (len, _) = self.lens();
+        unsafe { std::slice::from_raw_parts_mut((*self.table.get()).as_mut_ptr().cast(), len) }
+    }
+
+    /// The descriptor at `index` in the overflow table, if it's in use.
+    fn overflow_ptr(&self, index: usize) -> Option<*mut Descriptor> {
+        let (_, len) = self.lens();
+        if index >= len {
+            return None;
         }
+        let chunk = self.overflow[index / OVERFLOW_CHUNK_LEN];
+        Some(unsafe { chunk.add(index % OVERFLOW_CHUNK_LEN) })
     }
 
+    // Only `path_open` and `sock_accept` open new descriptors.
+    #[cfg_attr(not(any(filesystem, sockets)), allow(dead_code))]
     pub fn open(&mut self, d: Descriptor) -> Result<Fd, Errno> {
         match self.closed {
             // No closed descriptors: expand table
@@ -226,7 +381,7 @@ impl Descriptors {
                 let freelist_desc = self.get_mut(freelist_head).trapping_unwrap();
                 let next_closed = match freelist_desc {
                     Descriptor::Closed(next) => *next,
-                    _ => unreachable!("impossible: freelist points to a closed descriptor"),
+                    _ => unreachable!("impossible: freelist points to open fd {}", freelist_head),
                 };
                 // Write descriptor to the entry at the nead of the list
                 *freelist_desc = d;
@@ -238,17 +393,24 @@ impl Descriptors {
     }
 
     pub fn get(&self, fd: Fd) -> Result<&Descriptor, Errno> {
-        self.table()
-            .get(usize::try_from(fd).trapping_unwrap())
-            .ok_or(wasi::ERRNO_BADF)
+        let index = usize::try_from(fd).trapping_unwrap();
+        match index.checked_sub(INLINE_DESCRIPTORS) {
+            None => self.table().get(index),
+            Some(index) => self.overflow_ptr(index).map(|p| unsafe { &*p }),
+        }
+        .ok_or(wasi::ERRNO_BADF)
     }
 
     pub fn get_mut(&mut self, fd: Fd) -> Result<&mut Descriptor, Errno> {
-        self.table_mut()
-            .get_mut(usize::try_from(fd).trapping_unwrap())
-            .ok_or(wasi::ERRNO_BADF)
+        let index = usize::try_from(fd).trapping_unwrap();
+        match index.checked_sub(INLINE_DESCRIPTORS) {
+            None => self.table_mut().get_mut(index),
+            Some(index) => self.overflow_ptr(index).map(|p| unsafe { &mut *p }),
+        }
+        .ok_or(wasi::ERRNO_BADF)
     }
 
+    #[cfg(filesystem)]
     pub fn get_preopen(&self, fd: Fd) -> Option<&Preopen> {
         let preopens = self.preopens.get().trapping_unwrap();
         // Subtract 3 for the stdio indices to compute the preopen index.
@@ -288,8 +450,13 @@ impl Descriptors {
     pub fn renumber(&mut self, from_fd: Fd, to_fd: Fd) -> Result<(), Errno> {
         // First, ensure from_fd is in bounds:
         drop(self.get(from_fd)?);
+        // Like `dup2`, refuse to_fd beyond the descriptor limit rather than
+        // filling the table with closed descriptors up to it.
+        if usize::try_from(to_fd).trapping_unwrap() >= MAX_DESCRIPTORS {
+            return Err(wasi::ERRNO_BADF);
+        }
         // Expand table until to_fd is in bounds as well:
-        while self.table_len.get() as u32 <= to_fd as u32 {
+        while self.table_len.get() <= to_fd {
             self.push_closed()?;
         }
         // Then, close from_fd and put its contents into to_fd:
@@ -302,6 +469,27 @@ impl Descriptors {
 
     // A bunch of helper functions implemented in terms of the above pub functions:
 
+    /// Return the base and inheriting rights of `fd`.
+    pub fn get_rights(&self, fd: Fd) -> Result<(Rights, Rights), Errno> {
+        match self.get(fd)? {
+            Descriptor::Streams(streams) => {
+                Ok((streams.rights_base.get(), streams.rights_inheriting.get()))
+            }
+            Descriptor::Closed(_) => Err(wasi::ERRNO_BADF),
+        }
+    }
+
+    /// Fail with `ERRNO_NOTCAPABLE` unless `fd` has all of `rights`.
+    pub fn check_rights(&self, fd: Fd, rights: Rights) -> Result<(), Errno> {
+        let (base, _) = self.get_rights(fd)?;
+        if base & rights == rights {
+            Ok(())
+        } else {
+            Err(wasi::ERRNO_NOTCAPABLE)
+        }
+    }
+
+    #[cfg(filesystem)]
     pub fn get_stream_with_error(&self, fd: Fd, error: Errno) -> Result<&Streams, Errno> {
         match self.get(fd)? {
             Descriptor::Streams(streams) => Ok(streams),
@@ -309,6 +497,7 @@ impl Descriptors {
         }
     }
 
+    #[cfg(filesystem)]
     pub fn get_file_with_error(&self, fd: Fd, error: Errno) -> Result<&File, Errno> {
         match self.get(fd)? {
             Descriptor::Streams(Streams {
@@ -320,30 +509,34 @@ impl Descriptors {
         }
     }
 
-    #[allow(dead_code)] // until Socket is implemented
     pub fn get_socket(&self, fd: Fd) -> Result<crate::bindings::tcp::TcpSocket, Errno> {
         match self.get(fd)? {
+            #[cfg(sockets)]
             Descriptor::Streams(Streams {
                 type_: StreamType::Socket(socket),
                 ..
             }) => Ok(*socket),
             Descriptor::Closed(_) => Err(wasi::ERRNO_BADF),
-            _ => Err(wasi::ERRNO_INVAL),
+            _ => Err(wasi::ERRNO_NOTSOCK),
         }
     }
 
+    #[cfg(filesystem)]
     pub fn get_file(&self, fd: Fd) -> Result<&File, Errno> {
         self.get_file_with_error(fd, wasi::ERRNO_INVAL)
     }
 
+    #[cfg(filesystem)]
     pub fn get_dir(&self, fd: Fd) -> Result<&File, Errno> {
         self.get_file_with_error(fd, wasi::ERRNO_NOTDIR)
     }
 
+    #[cfg(filesystem)]
     pub fn get_seekable_file(&self, fd: Fd) -> Result<&File, Errno> {
         self.get_file_with_error(fd, wasi::ERRNO_SPIPE)
     }
 
+    #[cfg(filesystem)]
     pub fn get_seekable_stream(&self, fd: Fd) -> Result<&Streams, Errno> {
         self.get_stream_with_error(fd, wasi::ERRNO_SPIPE)
     }
@@ -363,14 +556,127 @@ impl Descriptors {
     }
 }
 
+/// Open the directory named by `pwd`, relative to the preopen with the
+/// longest name which is one of its ancestors.
+///
+/// wasi-libc resolves a relative path against a preopen named `.`, so the
+/// directory becomes one. But wasi-libc ignores a leading `/` or `./` in the
+/// names of preopens, so a preopen named `.` or `/` already matches every
+/// path, in which case this leaves the preopens alone.
+#[cfg(filesystem)]
+fn open_cwd(preopens: &[Preopen], pwd: &[u8]) -> Option<filesystem::Descriptor> {
+    if !matches!(pwd, [b'/', ..]) {
+        return None;
+    }
+
+    let mut best: Option<(&Preopen, &[u8])> = None;
+    for preopen in preopens {
+        let name = unsafe { core::slice::from_raw_parts(preopen.path.ptr, preopen.path.len) };
+        // Bail out if this preopen matches every path.
+        next_component(name)?;
+        if let Some(rest) = strip_dir(pwd, name) {
+            match best {
+                Some((_, best_rest)) if best_rest.len() <= rest.len() => {}
+                _ => best = Some((preopen, rest)),
+            }
+        }
+    }
+    let (preopen, rest) = best?;
+
+    let dot = [b'.'];
+    let path = match rest.iter().position(|b| *b != b'/') {
+        Some(start) => &rest[start..],
+        None => &dot[..],
+    };
+    filesystem::open_at(
+        preopen.descriptor,
+        filesystem::PathFlags::SYMLINK_FOLLOW,
+        path,
+        filesystem::OpenFlags::DIRECTORY,
+        filesystem::DescriptorFlags::READ,
+        filesystem::Modes::READABLE,
+    )
+    .ok()
+}
+
+/// Return a copy of `preopens` with `cwd` added at the end, named `.`.
+#[cfg(filesystem)]
+fn add_cwd_preopen(
+    preopens: &[Preopen],
+    cwd: filesystem::Descriptor,
+    arena: &BumpArena,
+) -> &'static [Preopen] {
+    let len = preopens.len() + 1;
+    unsafe {
+        let name = arena.alloc(1, 1);
+        name.write(b'.');
+
+        let base = arena
+            .alloc(
+                core::mem::align_of::<Preopen>(),
+                len * core::mem::size_of::<Preopen>(),
+            )
+            .cast::<Preopen>();
+        core::ptr::copy_nonoverlapping(preopens.as_ptr(), base, preopens.len());
+        base.add(preopens.len()).write(Preopen {
+            descriptor: cwd,
+            path: WasmStr { ptr: name, len: 1 },
+        });
+        core::slice::from_raw_parts(base, len)
+    }
+}
+
+/// If `dir` names `path` or one of its ancestors, return the rest of `path`.
+#[cfg(filesystem)]
+fn strip_dir<'a>(mut path: &'a [u8], mut dir: &[u8]) -> Option<&'a [u8]> {
+    while let Some((want, dir_rest)) = next_component(dir) {
+        let (component, rest) = next_component(path)?;
+        if component != want {
+            return None;
+        }
+        path = rest;
+        dir = dir_rest;
+    }
+    Some(path)
+}
+
+/// Split the first component off `path`, skipping separators and `.`
+/// components.
+#[cfg(filesystem)]
+fn next_component(mut path: &[u8]) -> Option<(&[u8], &[u8])> {
+    loop {
+        match path {
+            [] => return None,
+            [b'/', rest @ ..] => path = rest,
+            _ => {
+                let end = path.iter().position(|b| *b == b'/').unwrap_or(path.len());
+                let (component, rest) = path.split_at(end);
+                if !matches!(component, [b'.']) {
+                    return Some((component, rest));
+                }
+                path = rest;
+            }
+        }
+    }
+}
+
+#[cfg(filesystem)]
 #[repr(C)]
 pub struct Preopen {
     pub descriptor: u32,
     pub path: WasmStr,
 }
 
+#[cfg(filesystem)]
 #[repr(C)]
 pub struct PreopenList {
     pub base: *const Preopen,
     pub len: usize,
 }
+
+#[cfg(sockets)]
+#[repr(C)]
+pub struct SocketList {
+    pub base: *const tcp::TcpSocket,
+    pub len: usize,
+}
@@ -1,28 +1,65 @@
-#![allow(unused_variables)] // TODO: remove this when more things are implemented
-
-use crate::bindings::{
-    exit, filesystem, monotonic_clock, network, poll, random, streams, wall_clock,
-};
-use core::cell::{Cell, RefCell, RefMut, UnsafeCell};
+// TODO: remove this when more things are implemented
+#![allow(unused_variables)]
+
+#[cfg(not(feature = "proxy"))]
+use crate::bindings::{cpu_clock, exit, filesystem, monotonic_clock, streams, wall_clock};
+#[cfg(sockets)]
+use crate::bindings::{network, tcp};
+use crate::bindings::{poll, random};
+#[cfg(not(feature = "proxy"))]
+use core::cell::RefMut;
+#[cfg(filesystem)]
+use core::cell::UnsafeCell;
+use core::cell::{Cell, RefCell};
+#[cfg(filesystem)]
 use core::cmp::min;
+#[cfg(not(feature = "proxy"))]
 use core::ffi::c_void;
+#[cfg(not(feature = "proxy"))]
 use core::hint::black_box;
-use core::mem::{self, align_of, forget, size_of, ManuallyDrop, MaybeUninit};
+#[cfg(not(feature = "proxy"))]
+use core::mem::align_of;
+#[cfg(filesystem)]
+use core::mem::ManuallyDrop;
+use core::mem::{self, forget, size_of, MaybeUninit};
+#[cfg(not(feature = "proxy"))]
 use core::ops::{Deref, DerefMut};
 use core::ptr::{self, null_mut};
+#[cfg(not(feature = "proxy"))]
 use core::slice;
+#[cfg(not(feature = "proxy"))]
 use poll::Pollable;
 use wasi::*;
 
-#[cfg(all(feature = "command", feature = "reactor"))]
-compile_error!("only one of the `command` and `reactor` features may be selected at a time");
+#[cfg(any(
+    all(feature = "command", feature = "reactor"),
+    all(feature = "command", feature = "proxy"),
+    all(feature = "reactor", feature = "proxy"),
+))]
+compile_error!(
+    "only one of the `command`, `reactor` and `proxy` features may be selected at a time"
+);
+
+#[cfg(all(feature = "proxy", any(filesystem, sockets)))]
+compile_error!("the `proxy` world has no filesystem or sockets to enable");
 
 #[macro_use]
 mod macros;
 
+#[cfg(not(feature = "proxy"))]
 mod descriptors;
+#[cfg(not(feature = "proxy"))]
 use crate::descriptors::{Descriptor, Descriptors, StreamType, Streams};
 
+#[cfg(feature = "proxy")]
+mod proxy;
+
+#[cfg(feature = "trace")]
+mod trace;
+
+#[cfg(all(not(feature = "proxy"), not(all(filesystem, sockets))))]
+mod nosys;
+
 pub mod bindings {
     #[cfg(feature = "command")]
     wit_bindgen::generate!({
@@ -31,7 +68,7 @@ pub mod bindings {
         raw_strings,
         // The generated definition of command will pull in std, so we are defining it
         // manually below instead
-        skip: ["main", "get-directories", "get-environment"],
+        skip: ["main", "get-directories", "get-sockets", "get-environment", "poll-oneoff-readiness"],
     });
 
     #[cfg(feature = "reactor")]
@@ -39,13 +76,27 @@ pub mod bindings {
         world: "reactor",
         std_feature,
         raw_strings,
-        skip: ["get-directories", "get-environment"],
+        skip: ["get-directories", "get-sockets", "get-environment", "poll-oneoff-readiness"],
+    });
+
+    #[cfg(feature = "proxy")]
+    wit_bindgen::generate!({
+        world: "proxy",
+        std_feature,
+        raw_strings,
     });
 }
 
 // The unwrap/expect methods in std pull panic when they fail, which pulls
 // in unwinding machinery that we can't use in the adapter. Instead, use this
 // extension trait to get postfixed upwrap on Option and Result.
+//
+// Trapping kills the whole instance, so it's reserved for broken invariants
+// of the adapter itself, such as memory-safety violations. Arguments and
+// host behavior which the adapter doesn't support are reported with an
+// errno instead, usually `ERRNO_INVAL` for unknown values, `ERRNO_BADF` for
+// descriptors of the wrong kind, and `ERRNO_NOTSUP` for unsupported
+// operations, so that programs probing for features can carry on.
 trait TrappingUnwrap<T> {
     fn trapping_unwrap(self) -> T;
 }
@@ -86,11 +137,27 @@ pub unsafe extern "C" fn cabi_import_realloc(
     ptr
 }
 
+#[link(wasm_import_module = "__main_module__")]
+extern "C" {
+    /// The main module's allocator, used for the `State` itself and for data
+    /// which outgrows it.
+    fn cabi_realloc(old_ptr: *mut u8, old_len: usize, align: usize, new_len: usize) -> *mut u8;
+}
+
 /// Bump-allocated memory arena. This is a singleton - the
-/// memory will be sized according to `bump_arena_size()`.
+/// memory will be sized according to `bump_arena_size()`, and allocations
+/// which don't fit spill into chunks from the main module's allocator.
 pub struct BumpArena {
     data: MaybeUninit<[u8; bump_arena_size()]>,
     position: Cell<usize>,
+    spill: Spill,
+}
+
+/// The chunk of memory the arena is currently spilling into, if any.
+struct Spill {
+    chunk: Cell<*mut u8>,
+    position: Cell<usize>,
+    len: Cell<usize>,
 }
 
 impl BumpArena {
@@ -98,6 +165,11 @@ impl BumpArena {
         BumpArena {
             data: MaybeUninit::uninit(),
             position: Cell::new(0),
+            spill: Spill {
+                chunk: Cell::new(null_mut()),
+                position: Cell::new(0),
+                len: Cell::new(0),
+            },
         }
     }
     fn alloc(&self, align: usize, size: usize) -> *mut u8 {
@@ -106,16 +178,113 @@ impl BumpArena {
         let alloc = align_to(next, align);
         let offset = alloc - start;
         if offset + size > bump_arena_size() {
-            unreachable!("out of memory");
+            return self.alloc_spill(align, size);
         }
         self.position.set(offset + size);
         alloc as *mut u8
     }
+
+    #[cold]
+    fn alloc_spill(&self, align: usize, size: usize) -> *mut u8 {
+        let start = self.spill.chunk.get() as usize;
+        if start != 0 {
+            let alloc = align_to(start + self.spill.position.get(), align);
+            let offset = alloc - start;
+            if offset.checked_add(size).trapping_unwrap() <= self.spill.len.get() {
+                self.spill.position.set(offset + size);
+                return alloc as *mut u8;
+            }
+        }
+
+        // Start a new chunk of at least a page. The rest of the previous
+        // chunk is abandoned, but everything allocated from it stays valid,
+        // as the arena is never freed.
+        let len = align_to(size.max(1), PAGE_SIZE);
+        let chunk = alloc_pages(align, len);
+        if chunk.is_null() {
+            unreachable!("out of memory allocating {} bytes", len);
+        }
+        self.spill.chunk.set(chunk);
+        self.spill.position.set(size);
+        self.spill.len.set(len);
+        chunk
+    }
+}
+
+/// The alignment of the memory in an `Overflow` buffer.
+const OVERFLOW_ALIGN: usize = 8;
+
+/// A buffer of whole pages for data which doesn't fit in the fixed-size
+/// buffers in `State`, such as unusually long file names. It's reused, and
+/// replaced by a bigger one as needed, abandoning the old one, as the
+/// canonical ABI has no way to free memory.
+struct Overflow {
+    ptr: Cell<*mut u8>,
+    len: Cell<usize>,
+}
+
+impl Overflow {
+    fn new() -> Self {
+        Overflow {
+            ptr: Cell::new(null_mut()),
+            len: Cell::new(0),
+        }
+    }
+
+    /// Return a pointer to at least `size` bytes of the buffer, replacing it
+    /// if needed. This invalidates anything previously stored in the buffer
+    /// unless it's already at least `size` bytes.
+    fn reserve(&self, align: usize, size: usize) -> *mut u8 {
+        if align > OVERFLOW_ALIGN {
+            unreachable!("overflow alignment {}", align)
+        }
+        let len = self.len.get();
+        if len < size.max(1) {
+            let new_len = align_to(
+                size.max(1).max(len.checked_mul(2).trapping_unwrap()),
+                PAGE_SIZE,
+            );
+            let ptr = alloc_pages(OVERFLOW_ALIGN, new_len);
+            if ptr.is_null() {
+                unreachable!("out of memory allocating {} bytes", new_len);
+            }
+            self.ptr.set(ptr);
+            self.len.set(new_len);
+        }
+        self.ptr.get()
+    }
 }
+
 fn align_to(ptr: usize, align: usize) -> usize {
     (ptr + (align - 1)) & !(align - 1)
 }
 
+/// Allocate `len` bytes of fresh memory, a multiple of `PAGE_SIZE`, for data
+/// which outgrows `State`. Returns null if there's no more memory.
+///
+/// A single page comes from the main module's `cabi_realloc`, as `State`
+/// does. That's all that can be asked of it: when the main module doesn't
+/// export `cabi_realloc`, wit-component provides one which grows memory by a
+/// page at a time, and traps on any other request, including reallocation.
+/// Bigger allocations come straight from `memory.grow`, as that fallback's
+/// pages do.
+pub(crate) fn alloc_pages(align: usize, len: usize) -> *mut u8 {
+    assert!(len.is_multiple_of(PAGE_SIZE) && align <= PAGE_SIZE);
+    if len == PAGE_SIZE {
+        return unsafe { cabi_realloc(null_mut(), 0, align, PAGE_SIZE) };
+    }
+    #[cfg(target_arch = "wasm32")]
+    let old = core::arch::wasm32::memory_grow(0, len / PAGE_SIZE);
+    // This is here to keep rust-analyzer happy when building for native,
+    // which has no `memory.grow`:
+    #[cfg(not(target_arch = "wasm32"))]
+    let old = usize::MAX;
+    if old == usize::MAX {
+        return null_mut();
+    }
+    (old * PAGE_SIZE) as *mut u8
+}
+
 // Invariant: buffer not-null and arena is-some are never true at the same
 // time. We did not use an enum to make this invalid behavior unrepresentable
 // because we can't use RefCell to borrow() the variants of the enum - only
@@ -128,6 +297,8 @@ pub struct ImportAlloc {
     len: Cell<usize>,
     // When not-empty, allocator should use this arena to satisfy allocations.
     arena: Cell<Option<&'static BumpArena>>,
+    // Satisfies an allocation which doesn't fit in `buffer`.
+    overflow: Overflow,
 }
 
 impl ImportAlloc {
@@ -136,12 +307,17 @@ impl ImportAlloc {
             buffer: Cell::new(std::ptr::null_mut()),
             len: Cell::new(0),
             arena: Cell::new(None),
+            overflow: Overflow::new(),
         }
     }
 
     /// Expect at most one import allocation during execution of the provided closure.
     /// Use the provided buffer to satisfy that import allocation. The user is responsible
     /// for making sure allocated imports are not used beyond the lifetime of the buffer.
+    ///
+    /// If the allocation doesn't fit in the buffer, it's satisfied from the overflow
+    /// buffer instead, which is only valid until the next allocation which overflows.
+    /// Users which can receive more data than the buffer holds must check where it went.
     fn with_buffer<T>(&self, buffer: *mut u8, len: usize, f: impl FnOnce() -> T) -> T {
         if self.arena.get().is_some() {
             unreachable!("arena mode")
@@ -159,6 +335,7 @@ impl ImportAlloc {
     /// Permit many import allocations during execution of the provided closure.
     /// Use the provided BumpArena to satisfry those allocations. The user is responsible
     /// for making sure allocated imports are not used beyond the lifetime of the arena.
+    #[cfg(not(feature = "proxy"))]
     fn with_arena<T>(&self, arena: &BumpArena, f: impl FnOnce() -> T) -> T {
         if !self.buffer.get().is_null() {
             unreachable!("buffer mode")
@@ -186,12 +363,12 @@ impl ImportAlloc {
             }
             let buffer = buffer as usize;
             let alloc = align_to(buffer, align);
+            self.buffer.set(std::ptr::null_mut());
             if alloc.checked_add(size).trapping_unwrap()
                 > buffer.checked_add(self.len.get()).trapping_unwrap()
             {
-                unreachable!("out of memory")
+                return self.overflow.reserve(align, size);
             }
-            self.buffer.set(std::ptr::null_mut());
             alloc as *mut u8
         }
     }
@@ -199,11 +376,10 @@ impl ImportAlloc {
 
 /// This allocator is only used for the `main` entrypoint.
 ///
-/// The implementation here is a bump allocator into `State::long_lived_arena` which
-/// traps when it runs out of data. This means that the total size of
-/// arguments/env/etc coming into a component is bounded by the current 64k
-/// (ish) limit. That's just an implementation limit though which can be lifted
-/// by dynamically calling the main module's allocator as necessary for more data.
+/// The implementation here is a bump allocator into `State::long_lived_arena`,
+/// which spills into memory from the main module's allocator once the space
+/// in `State` runs out, so arguments/env/etc coming into a component aren't
+/// bounded by the size of `State`.
 #[no_mangle]
 pub unsafe extern "C" fn cabi_export_realloc(
     old_ptr: *mut u8,
@@ -224,7 +400,8 @@ pub unsafe extern "C" fn cabi_export_realloc(
 
 /// Read command-line argument data.
 /// The size of the array should match that returned by `args_sizes_get`
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn args_get(mut argv: *mut *mut u8, mut argv_buf: *mut u8) -> Errno {
     State::with(|state| {
         for arg in state.get_args() {
@@ -246,7 +423,8 @@ pub unsafe extern "C" fn args_get(mut argv: *mut *mut u8, mut argv_buf: *mut u8)
 }
 
 /// Return command-line argument data sizes.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn args_sizes_get(argc: *mut Size, argv_buf_size: *mut Size) -> Errno {
     State::with(|state| {
         let args = state.get_args();
@@ -260,7 +438,8 @@ pub unsafe extern "C" fn args_sizes_get(argc: *mut Size, argv_buf_size: *mut Siz
 
 /// Read environment variable data.
 /// The sizes of the buffers should match that returned by `environ_sizes_get`.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn environ_get(environ: *mut *mut u8, environ_buf: *mut u8) -> Errno {
     State::with(|state| {
         let mut offsets = environ;
@@ -287,7 +466,8 @@ pub unsafe extern "C" fn environ_get(environ: *mut *mut u8, environ_buf: *mut u8
 }
 
 /// Return environment variable data sizes.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn environ_sizes_get(
     environc: *mut Size,
     environ_buf_size: *mut Size,
@@ -320,7 +500,8 @@ pub unsafe extern "C" fn environ_sizes_get(
 /// Implementations are required to provide a non-zero value for supported clocks. For unsupported clocks,
 /// return `errno::inval`.
 /// Note: This is similar to `clock_getres` in POSIX.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub extern "C" fn clock_res_get(id: Clockid, resolution: &mut Timestamp) -> Errno {
     State::with(|state| {
         match id {
@@ -328,6 +509,11 @@ pub extern "C" fn clock_res_get(id: Clockid, resolution: &mut Timestamp) -> Errn
                 let res = monotonic_clock::resolution();
                 *resolution = res;
             }
+            // An instance only ever has one thread, so its thread and process
+            // CPU times are both the instance's CPU time.
+            CLOCKID_PROCESS_CPUTIME_ID | CLOCKID_THREAD_CPUTIME_ID => {
+                *resolution = cpu_clock::resolution();
+            }
             CLOCKID_REALTIME => {
                 let res = wall_clock::resolution();
                 *resolution = Timestamp::from(res.seconds)
@@ -335,7 +521,7 @@ pub extern "C" fn clock_res_get(id: Clockid, resolution: &mut Timestamp) -> Errn
                     .and_then(|ns| ns.checked_add(res.nanoseconds.into()))
                     .ok_or(ERRNO_OVERFLOW)?;
             }
-            _ => unreachable!(),
+            _ => return Err(ERRNO_INVAL),
         }
         Ok(())
     })
@@ -343,7 +529,8 @@ pub extern "C" fn clock_res_get(id: Clockid, resolution: &mut Timestamp) -> Errn
 
 /// Return the time value of a clock.
 /// Note: This is similar to `clock_gettime` in POSIX.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn clock_time_get(
     id: Clockid,
     _precision: Timestamp,
@@ -358,6 +545,10 @@ pub unsafe extern "C" fn clock_time_get(
                 CLOCKID_MONOTONIC => {
                     *time = monotonic_clock::now();
                 }
+                // See `clock_res_get` for why both use the same clock.
+                CLOCKID_PROCESS_CPUTIME_ID | CLOCKID_THREAD_CPUTIME_ID => {
+                    *time = cpu_clock::now();
+                }
                 CLOCKID_REALTIME => {
                     let res = wall_clock::now();
                     *time = Timestamp::from(res.seconds)
@@ -365,7 +556,7 @@ pub unsafe extern "C" fn clock_time_get(
                         .and_then(|ns| ns.checked_add(res.nanoseconds.into()))
                         .ok_or(ERRNO_OVERFLOW)?;
                 }
-                _ => unreachable!(),
+                _ => return Err(ERRNO_INVAL),
             }
             Ok(())
         })
@@ -377,7 +568,8 @@ pub unsafe extern "C" fn clock_time_get(
 
 /// Provide file advisory information on a file descriptor.
 /// Note: This is similar to `posix_fadvise` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_advise(
     fd: Fd,
     offset: Filesize,
@@ -395,6 +587,7 @@ pub unsafe extern "C" fn fd_advise(
     };
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_ADVISE)?;
         let file = ds.get_seekable_file(fd)?;
         filesystem::advise(file.fd, offset, len, advice)?;
         Ok(())
@@ -403,19 +596,28 @@ pub unsafe extern "C" fn fd_advise(
 
 /// Force the allocation of space in a file.
 /// Note: This is similar to `posix_fallocate` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_allocate(fd: Fd, offset: Filesize, len: Filesize) -> Errno {
-    unreachable!("fd_allocate")
+    State::with(|state| {
+        let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_ALLOCATE)?;
+        let file = ds.get_seekable_file(fd)?;
+        filesystem::allocate(file.fd, offset, len)?;
+        Ok(())
+    })
 }
 
 /// Close a file descriptor.
 /// Note: This is similar to `close` in POSIX.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_close(fd: Fd) -> Errno {
     State::with_mut(|state| {
         // If there's a dirent cache entry for this file descriptor then drop
         // it since the descriptor is being closed and future calls to
         // `fd_readdir` should return an error.
+        #[cfg(filesystem)]
         if fd == state.dirent_cache.for_fd.get() {
             drop(state.dirent_cache.stream.replace(None));
         }
@@ -427,10 +629,12 @@ pub unsafe extern "C" fn fd_close(fd: Fd) -> Errno {
 
 /// Synchronize the data of a file to disk.
 /// Note: This is similar to `fdatasync` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_datasync(fd: Fd) -> Errno {
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_DATASYNC)?;
         let file = ds.get_file(fd)?;
         filesystem::sync_data(file.fd)?;
         Ok(())
@@ -439,11 +643,15 @@ pub unsafe extern "C" fn fd_datasync(fd: Fd) -> Errno {
 
 /// Get the attributes of a file descriptor.
 /// Note: This returns similar flags to `fsync(fd, F_GETFL)` in POSIX, as well as additional fields.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_fdstat_get(fd: Fd, stat: *mut Fdstat) -> Errno {
     State::with(|state| match state.descriptors().get(fd)? {
+        #[cfg(filesystem)]
         Descriptor::Streams(Streams {
             type_: StreamType::File(file),
+            rights_base,
+            rights_inheriting,
             ..
         }) => {
             let flags = filesystem::get_flags(file.fd)?;
@@ -452,7 +660,7 @@ pub unsafe extern "C" fn fd_fdstat_get(fd: Fd, stat: *mut Fdstat) -> Errno {
             let fs_filetype = type_.into();
 
             let mut fs_flags = 0;
-            let mut fs_rights_base = !0;
+            let mut fs_rights_base = rights_base.get();
             if !flags.contains(filesystem::DescriptorFlags::READ) {
                 fs_rights_base &= !RIGHTS_FD_READ;
             }
@@ -474,7 +682,7 @@ pub unsafe extern "C" fn fd_fdstat_get(fd: Fd, stat: *mut Fdstat) -> Errno {
             if file.append {
                 fs_flags |= FDFLAGS_APPEND;
             }
-            let fs_rights_inheriting = fs_rights_base;
+            let fs_rights_inheriting = rights_inheriting.get();
 
             stat.write(Fdstat {
                 fs_filetype,
@@ -487,23 +695,26 @@ pub unsafe extern "C" fn fd_fdstat_get(fd: Fd, stat: *mut Fdstat) -> Errno {
         Descriptor::Streams(Streams {
             input,
             output,
-            type_: StreamType::Socket(_),
-        })
-        | Descriptor::Streams(Streams {
-            input,
-            output,
-            type_: StreamType::Unknown,
+            type_,
+            rights_base,
+            rights_inheriting,
         }) => {
-            let fs_filetype = FILETYPE_UNKNOWN;
+            let fs_filetype = match type_ {
+                StreamType::Stdio(filetype) => *filetype,
+                #[cfg(sockets)]
+                StreamType::Socket(_) => FILETYPE_SOCKET_STREAM,
+                #[cfg(filesystem)]
+                StreamType::File(_) => return Err(ERRNO_BADF),
+            };
             let fs_flags = 0;
-            let mut fs_rights_base = 0;
-            if input.get().is_some() {
-                fs_rights_base |= RIGHTS_FD_READ;
+            let mut fs_rights_base = rights_base.get();
+            if input.get().is_none() {
+                fs_rights_base &= !RIGHTS_FD_READ;
             }
-            if output.get().is_some() {
-                fs_rights_base |= RIGHTS_FD_WRITE;
+            if output.get().is_none() {
+                fs_rights_base &= !RIGHTS_FD_WRITE;
             }
-            let fs_rights_inheriting = fs_rights_base;
+            let fs_rights_inheriting = rights_inheriting.get();
             stat.write(Fdstat {
                 fs_filetype,
                 fs_flags,
@@ -518,7 +729,8 @@ pub unsafe extern "C" fn fd_fdstat_get(fd: Fd, stat: *mut Fdstat) -> Errno {
 
 /// Adjust the flags associated with a file descriptor.
 /// Note: This is similar to `fcntl(fd, F_SETFL, flags)` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_fdstat_set_flags(fd: Fd, flags: Fdflags) -> Errno {
     let mut new_flags = filesystem::DescriptorFlags::empty();
     if flags & FDFLAGS_DSYNC == FDFLAGS_DSYNC {
@@ -536,6 +748,7 @@ pub unsafe extern "C" fn fd_fdstat_set_flags(fd: Fd, flags: Fdflags) -> Errno {
 
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_FDSTAT_SET_FLAGS)?;
         let file = ds.get_file(fd)?;
         filesystem::set_flags(file.fd, new_flags)?;
         Ok(())
@@ -544,22 +757,38 @@ pub unsafe extern "C" fn fd_fdstat_set_flags(fd: Fd, flags: Fdflags) -> Errno {
 
 /// Adjust the rights associated with a file descriptor.
 /// This can only be used to remove rights, and returns `errno::notcapable` if called in a way that would attempt to add rights
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_fdstat_set_rights(
     fd: Fd,
     fs_rights_base: Rights,
     fs_rights_inheriting: Rights,
 ) -> Errno {
-    unreachable!()
+    State::with(|state| match state.descriptors().get(fd)? {
+        Descriptor::Streams(streams) => {
+            if fs_rights_base & !streams.rights_base.get() != 0
+                || fs_rights_inheriting & !streams.rights_inheriting.get() != 0
+            {
+                return Err(ERRNO_NOTCAPABLE);
+            }
+            streams.rights_base.set(fs_rights_base);
+            streams.rights_inheriting.set(fs_rights_inheriting);
+            Ok(())
+        }
+        Descriptor::Closed(_) => Err(ERRNO_BADF),
+    })
 }
 
 /// Return the attributes of an open file.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_filestat_get(fd: Fd, buf: *mut Filestat) -> Errno {
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_FILESTAT_GET)?;
 
         match ds.get(fd)? {
+            #[cfg(filesystem)]
             Descriptor::Streams(Streams {
                 type_: StreamType::File(file),
                 ..
@@ -578,16 +807,20 @@ pub unsafe extern "C" fn fd_filestat_get(fd: Fd, buf: *mut Filestat) -> Errno {
                 };
                 Ok(())
             }
-            // For unknown (effectively, stdio) streams, instead of returning an error, return a
-            // Filestat with all zero fields and Filetype::Unknown.
-            Descriptor::Streams(Streams {
-                type_: StreamType::Unknown,
-                ..
-            }) => {
+            // For stdio streams and sockets, instead of returning an error, return a
+            // Filestat with all zero fields except for the filetype.
+            Descriptor::Streams(Streams { type_, .. }) => {
+                let filetype = match type_ {
+                    StreamType::Stdio(filetype) => *filetype,
+                    #[cfg(sockets)]
+                    StreamType::Socket(_) => FILETYPE_SOCKET_STREAM,
+                    #[cfg(filesystem)]
+                    StreamType::File(_) => return Err(ERRNO_BADF),
+                };
                 *buf = Filestat {
                     dev: 0,
                     ino: 0,
-                    filetype: FILETYPE_UNKNOWN,
+                    filetype,
                     nlink: 0,
                     size: 0,
                     atim: 0,
@@ -603,10 +836,12 @@ pub unsafe extern "C" fn fd_filestat_get(fd: Fd, buf: *mut Filestat) -> Errno {
 
 /// Adjust the size of an open file. If this increases the file's size, the extra bytes are filled with zeros.
 /// Note: This is similar to `ftruncate` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_filestat_set_size(fd: Fd, size: Filesize) -> Errno {
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_FILESTAT_SET_SIZE)?;
         let file = ds.get_file(fd)?;
         filesystem::set_size(file.fd, size)?;
         Ok(())
@@ -615,7 +850,8 @@ pub unsafe extern "C" fn fd_filestat_set_size(fd: Fd, size: Filesize) -> Errno {
 
 /// Adjust the timestamps of an open file or directory.
 /// Note: This is similar to `futimens` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_filestat_set_times(
     fd: Fd,
     atim: Timestamp,
@@ -647,6 +883,7 @@ pub unsafe extern "C" fn fd_filestat_set_times(
 
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_FILESTAT_SET_TIMES)?;
         let file = ds.get_file(fd)?;
         filesystem::set_times(file.fd, atim, mtim)?;
         Ok(())
@@ -655,7 +892,8 @@ pub unsafe extern "C" fn fd_filestat_set_times(
 
 /// Read from a file descriptor, without using and updating the file descriptor's offset.
 /// Note: This is similar to `preadv` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_pread(
     fd: Fd,
     mut iovs_ptr: *const Iovec,
@@ -678,6 +916,7 @@ pub unsafe extern "C" fn fd_pread(
         let len = (*iovs_ptr).buf_len;
 
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_READ | RIGHTS_FD_SEEK)?;
         let file = ds.get_file(fd)?;
         let (data, end) = state
             .import_alloc
@@ -697,7 +936,8 @@ pub unsafe extern "C" fn fd_pread(
 }
 
 /// Return a description of the given preopened file descriptor.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_prestat_get(fd: Fd, buf: *mut Prestat) -> Errno {
     if matches!(
         get_allocation_state(),
@@ -725,7 +965,8 @@ pub unsafe extern "C" fn fd_prestat_get(fd: Fd, buf: *mut Prestat) -> Errno {
 }
 
 /// Return a description of the given preopened file descriptor.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_prestat_dir_name(fd: Fd, path: *mut u8, path_len: Size) -> Errno {
     State::with(|state| {
         if let Some(preopen) = state.descriptors().get_preopen(fd) {
@@ -743,7 +984,8 @@ pub unsafe extern "C" fn fd_prestat_dir_name(fd: Fd, path: *mut u8, path_len: Si
 
 /// Write to a file descriptor, without using and updating the file descriptor's offset.
 /// Note: This is similar to `pwritev` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_pwrite(
     fd: Fd,
     mut iovs_ptr: *const Ciovec,
@@ -766,6 +1008,7 @@ pub unsafe extern "C" fn fd_pwrite(
 
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_WRITE | RIGHTS_FD_SEEK)?;
         let file = ds.get_seekable_file(fd)?;
         let bytes = filesystem::write(file.fd, slice::from_raw_parts(ptr, len), offset)?;
         *nwritten = bytes as usize;
@@ -775,7 +1018,8 @@ pub unsafe extern "C" fn fd_pwrite(
 
 /// Read from a file descriptor.
 /// Note: This is similar to `readv` in POSIX.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_read(
     fd: Fd,
     mut iovs_ptr: *const Iovec,
@@ -796,7 +1040,9 @@ pub unsafe extern "C" fn fd_read(
     let len = (*iovs_ptr).buf_len;
 
     State::with(|state| {
-        match state.descriptors().get(fd)? {
+        let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_READ)?;
+        match ds.get(fd)? {
             Descriptor::Streams(streams) => {
                 let wasi_stream = streams.get_read_stream()?;
 
@@ -811,6 +1057,7 @@ pub unsafe extern "C" fn fd_read(
                 assert!(data.len() <= len);
 
                 // If this is a file, keep the current-position pointer up to date.
+                #[cfg(filesystem)]
                 if let StreamType::File(file) = &streams.type_ {
                     file.position
                         .set(file.position.get() + data.len() as filesystem::Filesize);
@@ -839,7 +1086,8 @@ pub unsafe extern "C" fn fd_read(
 /// truncating the last directory entry. This allows the caller to grow its
 /// read buffer size in case it's too small to fit a single large directory
 /// entry, or skip the oversized directory entry.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_readdir(
     fd: Fd,
     buf: *mut u8,
@@ -871,6 +1119,7 @@ pub unsafe extern "C" fn fd_readdir(
         // Compute the inode of `.` so that the iterator can produce an entry
         // for it.
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_READDIR)?;
         let dir = ds.get_dir(fd)?;
         let stat = filesystem::stat(dir.fd)?;
         let dot_inode = stat.inode;
@@ -1049,11 +1298,11 @@ pub unsafe extern "C" fn fd_readdir(
                 d_type: type_.into(),
             };
             // Extend the lifetime of `name` to the `self.state` lifetime for
-            // this iterator since the data for the name lives within state.
-            let name = unsafe {
-                assert_eq!(name.as_ptr(), self.state.path_buf.get().cast());
-                slice::from_raw_parts(name.as_ptr().cast(), name.len())
-            };
+            // this iterator since the data for the name lives within state,
+            // either in `path_buf` or, if it's longer than `PATH_MAX`, in the
+            // import allocator's overflow buffer. Either way it's used before
+            // the next entry is read.
+            let name = unsafe { slice::from_raw_parts(name.as_ptr().cast(), name.len()) };
             Some(Ok((dirent, name)))
         }
     }
@@ -1067,22 +1316,32 @@ pub unsafe extern "C" fn fd_readdir(
 /// thread at the same time.
 /// This function provides a way to atomically renumber file descriptors, which
 /// would disappear if `dup2()` were to be removed entirely.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_renumber(fd: Fd, to: Fd) -> Errno {
     State::with_mut(|state| state.descriptors_mut().renumber(fd, to))
 }
 
 /// Move the offset of a file descriptor.
 /// Note: This is similar to `lseek` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_seek(
     fd: Fd,
     offset: Filedelta,
     whence: Whence,
     newoffset: *mut Filesize,
 ) -> Errno {
+    // Querying the current position only requires the right to tell.
+    let rights = if whence == WHENCE_CUR && offset == 0 {
+        RIGHTS_FD_TELL
+    } else {
+        RIGHTS_FD_SEEK
+    };
+
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, rights)?;
         let stream = ds.get_seekable_stream(fd)?;
 
         // Seeking only works on files.
@@ -1108,10 +1367,12 @@ pub unsafe extern "C" fn fd_seek(
 
 /// Synchronize the data and metadata of a file to disk.
 /// Note: This is similar to `fsync` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_sync(fd: Fd) -> Errno {
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_SYNC)?;
         let file = ds.get_file(fd)?;
         filesystem::sync(file.fd)?;
         Ok(())
@@ -1120,10 +1381,12 @@ pub unsafe extern "C" fn fd_sync(fd: Fd) -> Errno {
 
 /// Return the current offset of a file descriptor.
 /// Note: This is similar to `lseek(fd, 0, SEEK_CUR)` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_tell(fd: Fd, offset: *mut Filesize) -> Errno {
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_FD_TELL)?;
         let file = ds.get_seekable_file(fd)?;
         *offset = file.position.get() as Filesize;
         Ok(())
@@ -1132,7 +1395,8 @@ pub unsafe extern "C" fn fd_tell(fd: Fd, offset: *mut Filesize) -> Errno {
 
 /// Write to a file descriptor.
 /// Note: This is similar to `writev` in POSIX.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn fd_write(
     fd: Fd,
     mut iovs_ptr: *const Ciovec,
@@ -1159,12 +1423,14 @@ pub unsafe extern "C" fn fd_write(
 
         State::with(|state| {
             let ds = state.descriptors();
+            ds.check_rights(fd, RIGHTS_FD_WRITE)?;
             match ds.get(fd)? {
                 Descriptor::Streams(streams) => {
                     let wasi_stream = streams.get_write_stream()?;
                     let bytes = streams::write(wasi_stream, bytes).map_err(|_| ERRNO_IO)?;
 
                     // If this is a file, keep the current-position pointer up to date.
+                    #[cfg(filesystem)]
                     if let StreamType::File(file) = &streams.type_ {
                         // But don't update if we're in append mode. Strictly speaking,
                         // we should set the position to the new end of the file, but
@@ -1189,7 +1455,8 @@ pub unsafe extern "C" fn fd_write(
 
 /// Create a directory.
 /// Note: This is similar to `mkdirat` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn path_create_directory(
     fd: Fd,
     path_ptr: *const u8,
@@ -1199,6 +1466,7 @@ pub unsafe extern "C" fn path_create_directory(
 
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_PATH_CREATE_DIRECTORY)?;
         let file = ds.get_dir(fd)?;
         filesystem::create_directory_at(file.fd, path)?;
         Ok(())
@@ -1207,7 +1475,8 @@ pub unsafe extern "C" fn path_create_directory(
 
 /// Return the attributes of a file or directory.
 /// Note: This is similar to `stat` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn path_filestat_get(
     fd: Fd,
     flags: Lookupflags,
@@ -1220,6 +1489,7 @@ pub unsafe extern "C" fn path_filestat_get(
 
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_PATH_FILESTAT_GET)?;
         let file = ds.get_dir(fd)?;
         let stat = filesystem::stat_at(file.fd, at_flags, path)?;
         let filetype = stat.type_.into();
@@ -1239,7 +1509,8 @@ pub unsafe extern "C" fn path_filestat_get(
 
 /// Adjust the timestamps of a file or directory.
 /// Note: This is similar to `utimensat` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn path_filestat_set_times(
     fd: Fd,
     flags: Lookupflags,
@@ -1277,6 +1548,7 @@ pub unsafe extern "C" fn path_filestat_set_times(
 
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_PATH_FILESTAT_SET_TIMES)?;
         let file = ds.get_dir(fd)?;
         filesystem::set_times_at(file.fd, at_flags, path, atim, mtim)?;
         Ok(())
@@ -1285,7 +1557,8 @@ pub unsafe extern "C" fn path_filestat_set_times(
 
 /// Create a hard link.
 /// Note: This is similar to `linkat` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn path_link(
     old_fd: Fd,
     old_flags: Lookupflags,
@@ -1300,8 +1573,11 @@ pub unsafe extern "C" fn path_link(
     let at_flags = at_flags_from_lookupflags(old_flags);
 
     State::with(|state| {
-        let old = state.descriptors().get_dir(old_fd)?.fd;
-        let new = state.descriptors().get_dir(new_fd)?.fd;
+        let ds = state.descriptors();
+        ds.check_rights(old_fd, RIGHTS_PATH_LINK_SOURCE)?;
+        ds.check_rights(new_fd, RIGHTS_PATH_LINK_TARGET)?;
+        let old = ds.get_dir(old_fd)?.fd;
+        let new = ds.get_dir(new_fd)?.fd;
         filesystem::link_at(old, at_flags, old_path, new, new_path)?;
         Ok(())
     })
@@ -1314,7 +1590,8 @@ pub unsafe extern "C" fn path_link(
 /// is error-prone in multi-threaded contexts. The returned file descriptor is
 /// guaranteed to be less than 2**31.
 /// Note: This is similar to `openat` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn path_open(
     fd: Fd,
     dirflags: Lookupflags,
@@ -1326,17 +1603,31 @@ pub unsafe extern "C" fn path_open(
     fdflags: Fdflags,
     opened_fd: *mut Fd,
 ) -> Errno {
-    drop(fs_rights_inheriting);
-
     let path = slice::from_raw_parts(path_ptr, path_len);
     let at_flags = at_flags_from_lookupflags(dirflags);
     let o_flags = o_flags_from_oflags(oflags);
-    let flags = descriptor_flags_from_flags(fs_rights_base, fdflags);
     let mode = filesystem::Modes::READABLE | filesystem::Modes::WRITEABLE;
     let append = fdflags & wasi::FDFLAGS_APPEND == wasi::FDFLAGS_APPEND;
 
+    let mut required = RIGHTS_PATH_OPEN;
+    if oflags & OFLAGS_CREAT == OFLAGS_CREAT {
+        required |= RIGHTS_PATH_CREATE_FILE;
+    }
+    if oflags & OFLAGS_TRUNC == OFLAGS_TRUNC {
+        required |= RIGHTS_PATH_FILESTAT_SET_SIZE;
+    }
+
     State::with_mut(|state| {
         let mut ds = state.descriptors_mut();
+        ds.check_rights(fd, required)?;
+
+        // The new descriptor gets the requested rights, narrowed to what the
+        // directory allows its descriptors to inherit.
+        let (_, inheriting) = ds.get_rights(fd)?;
+        let rights_base = fs_rights_base & inheriting;
+        let rights_inheriting = fs_rights_inheriting & inheriting;
+        let flags = descriptor_flags_from_flags(rights_base, fdflags);
+
         let file = ds.get_dir(fd)?;
         let result = filesystem::open_at(file.fd, at_flags, path, o_flags, flags, mode)?;
         let desc = Descriptor::Streams(Streams {
@@ -1347,6 +1638,8 @@ pub unsafe extern "C" fn path_open(
                 position: Cell::new(0),
                 append,
             }),
+            rights_base: Cell::new(rights_base),
+            rights_inheriting: Cell::new(rights_inheriting),
         });
 
         let fd = ds.open(desc)?;
@@ -1357,7 +1650,8 @@ pub unsafe extern "C" fn path_open(
 
 /// Read the contents of a symbolic link.
 /// Note: This is similar to `readlinkat` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn path_readlink(
     fd: Fd,
     path_ptr: *const u8,
@@ -1369,38 +1663,29 @@ pub unsafe extern "C" fn path_readlink(
     let path = slice::from_raw_parts(path_ptr, path_len);
 
     State::with(|state| {
-        // If the user gave us a buffer shorter than `PATH_MAX`, it may not be
-        // long enough to accept the actual path. `cabi_realloc` can't fail,
-        // so instead we handle this case specially.
-        let use_state_buf = buf_len < PATH_MAX;
-
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_PATH_READLINK)?;
         let file = ds.get_dir(fd)?;
-        let path = if use_state_buf {
-            state
-                .import_alloc
-                .with_buffer(state.path_buf.get().cast(), PATH_MAX, || {
-                    filesystem::readlink_at(file.fd, path)
-                })?
-        } else {
-            state
-                .import_alloc
-                .with_buffer(buf, buf_len, || filesystem::readlink_at(file.fd, path))?
-        };
 
-        assert_eq!(path.as_ptr(), buf);
-        assert!(path.len() <= buf_len);
-
-        *bufused = path.len();
-        if use_state_buf {
-            // Preview1 follows POSIX in truncating the returned path if it
-            // doesn't fit.
-            let len = min(path.len(), buf_len);
-            ptr::copy_nonoverlapping(path.as_ptr().cast(), buf, len);
+        // The path is read directly into `buf` if it fits, and otherwise into
+        // the import allocator's overflow buffer.
+        let path = state
+            .import_alloc
+            .with_buffer(buf, buf_len, || filesystem::readlink_at(file.fd, path))?;
+
+        // Preview1 follows POSIX in truncating the returned path if it
+        // doesn't fit, rather than failing with `ERRNO_RANGE`. `bufused` is
+        // then all of `buf_len`, which is how callers like Rust's standard
+        // library tell that it may be truncated, and retry with a bigger
+        // buffer.
+        let len = min(path.len(), buf_len);
+        if path.as_ptr() != buf {
+            ptr::copy_nonoverlapping(path.as_ptr(), buf, len);
         }
+        *bufused = len;
 
-        // The returned string's memory was allocated in `buf`, so don't separately
-        // free it.
+        // The returned string's memory was allocated in `buf` or the overflow
+        // buffer, so don't separately free it.
         forget(path);
 
         Ok(())
@@ -1410,7 +1695,8 @@ pub unsafe extern "C" fn path_readlink(
 /// Remove a directory.
 /// Return `errno::notempty` if the directory is not empty.
 /// Note: This is similar to `unlinkat(fd, path, AT_REMOVEDIR)` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn path_remove_directory(
     fd: Fd,
     path_ptr: *const u8,
@@ -1420,6 +1706,7 @@ pub unsafe extern "C" fn path_remove_directory(
 
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_PATH_REMOVE_DIRECTORY)?;
         let file = ds.get_dir(fd)?;
         filesystem::remove_directory_at(file.fd, path)?;
         Ok(())
@@ -1428,7 +1715,8 @@ pub unsafe extern "C" fn path_remove_directory(
 
 /// Rename a file or directory.
 /// Note: This is similar to `renameat` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn path_rename(
     old_fd: Fd,
     old_path_ptr: *const u8,
@@ -1442,6 +1730,8 @@ pub unsafe extern "C" fn path_rename(
 
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(old_fd, RIGHTS_PATH_RENAME_SOURCE)?;
+        ds.check_rights(new_fd, RIGHTS_PATH_RENAME_TARGET)?;
         let old = ds.get_dir(old_fd)?.fd;
         let new = ds.get_dir(new_fd)?.fd;
         filesystem::rename_at(old, old_path, new, new_path)?;
@@ -1451,7 +1741,8 @@ pub unsafe extern "C" fn path_rename(
 
 /// Create a symbolic link.
 /// Note: This is similar to `symlinkat` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn path_symlink(
     old_path_ptr: *const u8,
     old_path_len: usize,
@@ -1464,6 +1755,7 @@ pub unsafe extern "C" fn path_symlink(
 
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_PATH_SYMLINK)?;
         let file = ds.get_dir(fd)?;
         filesystem::symlink_at(file.fd, old_path, new_path)?;
         Ok(())
@@ -1473,24 +1765,28 @@ pub unsafe extern "C" fn path_symlink(
 /// Unlink a file.
 /// Return `errno::isdir` if the path refers to a directory.
 /// Note: This is similar to `unlinkat(fd, path, 0)` in POSIX.
-#[no_mangle]
+#[cfg(filesystem)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn path_unlink_file(fd: Fd, path_ptr: *const u8, path_len: usize) -> Errno {
     let path = slice::from_raw_parts(path_ptr, path_len);
 
     State::with(|state| {
         let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_PATH_UNLINK_FILE)?;
         let file = ds.get_dir(fd)?;
         filesystem::unlink_file_at(file.fd, path)?;
         Ok(())
     })
 }
 
+#[cfg(not(feature = "proxy"))]
 struct Pollables {
     pointer: *mut Pollable,
     index: usize,
     length: usize,
 }
 
+#[cfg(not(feature = "proxy"))]
 impl Pollables {
     unsafe fn push(&mut self, pollable: Pollable) {
         assert!(self.index < self.length);
@@ -1499,6 +1795,7 @@ impl Pollables {
     }
 }
 
+#[cfg(not(feature = "proxy"))]
 impl Drop for Pollables {
     fn drop(&mut self) {
         for i in 0..self.index {
@@ -1507,10 +1804,11 @@ impl Drop for Pollables {
     }
 }
 
+#[cfg(sockets)]
 impl From<network::Error> for Errno {
     fn from(error: network::Error) -> Errno {
         match error {
-            network::Error::Unknown => unreachable!(), // TODO
+            network::Error::Unknown => ERRNO_IO,
             network::Error::Again => ERRNO_AGAIN,
             /* TODO
             // Use a black box to prevent the optimizer from generating a
@@ -1522,14 +1820,15 @@ impl From<network::Error> for Errno {
             NetworkDown => ERRNO_NETDOWN,
             NetworkUnreachable => ERRNO_NETUNREACH,
             Timedout => ERRNO_TIMEDOUT,
-            _ => unreachable!(),
+            _ => ERRNO_IO,
             */
         }
     }
 }
 
 /// Concurrently poll for the occurrence of a set of events.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn poll_oneoff(
     r#in: *const Subscription,
     out: *mut Event,
@@ -1542,11 +1841,12 @@ pub unsafe extern "C" fn poll_oneoff(
 
     // We're going to split the `nevents` buffer into two non-overlapping
     // buffers: one to store the pollable handles, and the other to store
-    // the bool results.
+    // the readiness results.
     //
     // First, we assert that this is possible:
     assert!(align_of::<Event>() >= align_of::<Pollable>());
-    assert!(align_of::<Pollable>() >= align_of::<u8>());
+    assert!(align_of::<Event>() >= align_of::<Readiness>());
+    assert!(size_of::<Event>() % align_of::<Readiness>() == 0);
     assert!(
         nsubscriptions
             .checked_mul(size_of::<Event>())
@@ -1556,16 +1856,20 @@ pub unsafe extern "C" fn poll_oneoff(
                 .trapping_unwrap()
                 .checked_add(
                     nsubscriptions
-                        .checked_mul(size_of::<u8>())
+                        .checked_mul(size_of::<Readiness>())
                         .trapping_unwrap()
                 )
                 .trapping_unwrap()
     );
 
-    // Store the pollable handles at the beginning, and the bool results at the
-    // end, so that we don't clobber the bool results when writting the events.
+    // Store the pollable handles at the beginning, and the readiness results
+    // at the end, so that we don't clobber the readiness results when
+    // writting the events.
     let pollables = out as *mut c_void as *mut Pollable;
-    let results = out.add(nsubscriptions).cast::<u8>().sub(nsubscriptions);
+    let results = out
+        .add(nsubscriptions)
+        .cast::<Readiness>()
+        .sub(nsubscriptions);
 
     // Indefinite sleeping is not supported in preview1.
     if nsubscriptions == 0 {
@@ -1591,30 +1895,19 @@ pub unsafe extern "C" fn poll_oneoff(
                         == SUBCLOCKFLAGS_SUBSCRIPTION_CLOCK_ABSTIME;
                     match clock.id {
                         CLOCKID_REALTIME => {
+                            // The monotonic clock is the only one which can be
+                            // subscribed to, so wait for the time remaining
+                            // until an absolute deadline on it instead.
                             let timeout = if absolute {
-                                // Convert `clock.timeout` to `Datetime`.
-                                let mut datetime = wall_clock::Datetime {
-                                    seconds: clock.timeout / 1_000_000_000,
-                                    nanoseconds: (clock.timeout % 1_000_000_000) as _,
-                                };
-
-                                // Subtract `now`.
                                 let now = wall_clock::now();
-                                datetime.seconds -= now.seconds;
-                                if datetime.nanoseconds < now.nanoseconds {
-                                    datetime.seconds -= 1;
-                                    datetime.nanoseconds += 1_000_000_000;
-                                }
-                                datetime.nanoseconds -= now.nanoseconds;
-
-                                // Convert to nanoseconds.
-                                let nanos = datetime
+                                let now = now
                                     .seconds
                                     .checked_mul(1_000_000_000)
+                                    .and_then(|nanos| nanos.checked_add(now.nanoseconds.into()))
                                     .ok_or(ERRNO_OVERFLOW)?;
-                                nanos
-                                    .checked_add(datetime.nanoseconds.into())
-                                    .ok_or(ERRNO_OVERFLOW)?
+
+                                // A deadline in the past has already passed.
+                                clock.timeout.saturating_sub(now)
                             } else {
                                 clock.timeout
                             };
@@ -1629,29 +1922,50 @@ pub unsafe extern "C" fn poll_oneoff(
                 }
 
                 EVENTTYPE_FD_READ => {
-                    match state
+                    let fd = subscription.u.u.fd_read.file_descriptor;
+                    // Other errors are reported when the event is produced.
+                    if let Err(ERRNO_NOTCAPABLE) = state
                         .descriptors()
-                        .get_read_stream(subscription.u.u.fd_read.file_descriptor)
+                        .check_rights(fd, RIGHTS_POLL_FD_READWRITE)
                     {
+                        return Err(ERRNO_NOTCAPABLE);
+                    }
+                    let ds = state.descriptors();
+                    match ds.get_read_stream(fd) {
                         Ok(stream) => streams::subscribe_to_input_stream(stream),
-                        // If the file descriptor isn't a stream, request a
-                        // pollable which completes immediately so that it'll
-                        // immediately fail.
-                        Err(ERRNO_BADF) => monotonic_clock::subscribe(0, false),
+                        // A socket without streams is listening, and is
+                        // readable once it has a connection to accept.
+                        // Otherwise, if the file descriptor isn't a stream,
+                        // request a pollable which completes immediately so
+                        // that it'll immediately fail.
+                        Err(ERRNO_BADF) => match ds.get_socket(fd) {
+                            #[cfg(sockets)]
+                            Ok(socket) => tcp::subscribe(socket),
+                            _ => monotonic_clock::subscribe(0, false),
+                        },
                         Err(e) => return Err(e),
                     }
                 }
 
                 EVENTTYPE_FD_WRITE => {
-                    match state
+                    let fd = subscription.u.u.fd_write.file_descriptor;
+                    // Other errors are reported when the event is produced.
+                    if let Err(ERRNO_NOTCAPABLE) = state
                         .descriptors()
-                        .get_write_stream(subscription.u.u.fd_write.file_descriptor)
+                        .check_rights(fd, RIGHTS_POLL_FD_READWRITE)
                     {
+                        return Err(ERRNO_NOTCAPABLE);
+                    }
+                    let ds = state.descriptors();
+                    match ds.get_write_stream(fd) {
                         Ok(stream) => streams::subscribe_to_output_stream(stream),
-                        // If the file descriptor isn't a stream, request a
-                        // pollable which completes immediately so that it'll
-                        // immediately fail.
-                        Err(ERRNO_BADF) => monotonic_clock::subscribe(0, false),
+                        // As for reads, wait on listening sockets themselves,
+                        // and make anything else fail immediately.
+                        Err(ERRNO_BADF) => match ds.get_socket(fd) {
+                            #[cfg(sockets)]
+                            Ok(socket) => tcp::subscribe(socket),
+                            _ => monotonic_clock::subscribe(0, false),
+                        },
                         Err(e) => return Err(e),
                     }
                 }
@@ -1659,28 +1973,50 @@ pub unsafe extern "C" fn poll_oneoff(
                 _ => return Err(ERRNO_INVAL),
             });
         }
-        let vec = state.import_alloc.with_buffer(
-            results,
+        #[link(wasm_import_module = "poll")]
+        extern "C" {
+            #[link_name = "poll-oneoff-readiness"]
+            fn poll_oneoff_readiness_import(
+                in_ptr: *const Pollable,
+                in_len: usize,
+                rval: *mut ReadinessList,
+            );
+        }
+        let mut list = ReadinessList {
+            base: ptr::null(),
+            len: 0,
+        };
+        state.import_alloc.with_buffer(
+            results.cast(),
             nsubscriptions
-                .checked_mul(size_of::<bool>())
+                .checked_mul(size_of::<Readiness>())
                 .trapping_unwrap(),
-            || poll::poll_oneoff(slice::from_raw_parts(pollables.pointer, pollables.length)),
+            || poll_oneoff_readiness_import(pollables.pointer, pollables.length, &mut list),
         );
 
-        assert_eq!(vec.len(), nsubscriptions);
-        assert_eq!(vec.as_ptr(), results);
-        forget(vec);
+        assert_eq!(list.len, nsubscriptions);
+        assert_eq!(list.base, results as *const Readiness);
 
         drop(pollables);
 
-        let ready = subscriptions
-            .iter()
-            .enumerate()
-            .filter_map(|(i, s)| (*results.add(i) != 0).then_some(s));
+        let ready = subscriptions.iter().enumerate().filter_map(|(i, s)| {
+            // Copy the result out, as writing the events may overwrite it
+            // once it's been used.
+            let readiness = results.add(i).read();
+            (readiness.ready != 0).then_some((s, readiness))
+        });
 
         let mut count = 0;
 
-        for subscription in ready {
+        for (subscription, readiness) in ready {
+            // The host knows about the readiness of streams in general, while
+            // the adapter knows more about files in particular.
+            let hangup = if readiness.hangup != 0 {
+                EVENTRWFLAGS_FD_READWRITE_HANGUP
+            } else {
+                0
+            };
+
             let error;
             let type_;
             let nbytes;
@@ -1697,11 +2033,9 @@ pub unsafe extern "C" fn poll_oneoff(
                 EVENTTYPE_FD_READ => {
                     type_ = wasi::EVENTTYPE_FD_READ;
                     let ds = state.descriptors();
-                    let desc = ds
-                        .get(subscription.u.u.fd_read.file_descriptor)
-                        .trapping_unwrap();
-                    match desc {
-                        Descriptor::Streams(streams) => match &streams.type_ {
+                    match ds.get(subscription.u.u.fd_read.file_descriptor) {
+                        Ok(Descriptor::Streams(streams)) => match &streams.type_ {
+                            #[cfg(filesystem)]
                             StreamType::File(file) => match filesystem::stat(file.fd) {
                                 Ok(stat) => {
                                     error = ERRNO_SUCCESS;
@@ -1718,76 +2052,37 @@ pub unsafe extern "C" fn poll_oneoff(
                                     flags = 0;
                                 }
                             },
-                            StreamType::Socket(connection) => {
-                                unreachable!() // TODO
-                                               /*
-                                               match tcp::bytes_readable(*connection) {
-                                                   Ok(result) => {
-                                                       error = ERRNO_SUCCESS;
-                                                       nbytes = result.0;
-                                                       flags = if result.1 {
-                                                           EVENTRWFLAGS_FD_READWRITE_HANGUP
-                                                       } else {
-                                                           0
-                                                       };
-                                                   }
-                                                   Err(e) => {
-                                                       error = e.into();
-                                                       nbytes = 0;
-                                                       flags = 0;
-                                                   }
-                                               }
-                                               */
-                            }
-                            StreamType::Unknown => {
+                            _ => {
                                 error = ERRNO_SUCCESS;
-                                nbytes = 1;
-                                flags = 0;
+                                nbytes = readiness.nbytes;
+                                flags = hangup;
                             }
                         },
-                        _ => unreachable!(),
+                        Ok(Descriptor::Closed(_)) | Err(_) => {
+                            error = ERRNO_BADF;
+                            nbytes = 0;
+                            flags = 0;
+                        }
                     }
                 }
                 EVENTTYPE_FD_WRITE => {
                     type_ = wasi::EVENTTYPE_FD_WRITE;
                     let ds = state.descriptors();
-                    let desc = ds
-                        .get(subscription.u.u.fd_read.file_descriptor)
-                        .trapping_unwrap();
-                    match desc {
-                        Descriptor::Streams(streams) => match streams.type_ {
-                            StreamType::File(_) | StreamType::Unknown => {
-                                error = ERRNO_SUCCESS;
-                                nbytes = 1;
-                                flags = 0;
-                            }
-                            StreamType::Socket(connection) => {
-                                unreachable!() // TODO
-                                               /*
-                                               match tcp::bytes_writable(connection) {
-                                                   Ok(result) => {
-                                                       error = ERRNO_SUCCESS;
-                                                       nbytes = result.0;
-                                                       flags = if result.1 {
-                                                           EVENTRWFLAGS_FD_READWRITE_HANGUP
-                                                       } else {
-                                                           0
-                                                       };
-                                                   }
-                                                   Err(e) => {
-                                                       error = e.into();
-                                                       nbytes = 0;
-                                                       flags = 0;
-                                                   }
-                                               }
-                                               */
-                            }
-                        },
-                        _ => unreachable!(),
+                    match ds.get(subscription.u.u.fd_write.file_descriptor) {
+                        Ok(Descriptor::Streams(_)) => {
+                            error = ERRNO_SUCCESS;
+                            nbytes = readiness.nbytes;
+                            flags = hangup;
+                        }
+                        Ok(Descriptor::Closed(_)) | Err(_) => {
+                            error = ERRNO_BADF;
+                            nbytes = 0;
+                            flags = 0;
+                        }
                     }
                 }
 
-                _ => unreachable!(),
+                _ => return Err(ERRNO_INVAL),
             }
 
             *out.add(count) = Event {
@@ -1809,26 +2104,50 @@ pub unsafe extern "C" fn poll_oneoff(
 /// Terminate the process normally. An exit code of 0 indicates successful
 /// termination of the program. The meanings of other values is dependent on
 /// the environment.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn proc_exit(rval: Exitcode) -> ! {
-    let status = if rval == 0 { Ok(()) } else { Err(()) };
-    exit::exit(status); // does not return
+    exit::exit_with_code(rval); // does not return
     unreachable!("host exit implementation didn't exit!") // actually unreachable
 }
 
 /// Send a signal to the process of the calling thread.
 /// Note: This is similar to `raise` in POSIX.
-#[no_mangle]
+#[cfg(not(feature = "proxy"))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn proc_raise(sig: Signal) -> Errno {
-    unreachable!()
+    // There are no signal handlers, so each signal gets its default action.
+    match sig {
+        // `raise(0)` does nothing, as does a signal which is ignored by
+        // default. There's nothing to continue from for `SIGCONT` either.
+        SIGNAL_NONE | SIGNAL_CHLD | SIGNAL_CONT | SIGNAL_URG | SIGNAL_WINCH => ERRNO_SUCCESS,
+
+        // There's no way to stop a component.
+        SIGNAL_STOP | SIGNAL_TSTP | SIGNAL_TTIN | SIGNAL_TTOU => ERRNO_NOTSUP,
+
+        // Everything else terminates the process, which is reported like a
+        // shell would, with an exit status of 128 plus the signal number.
+        // Preview1 numbers signals like Linux, except that it omits
+        // `SIGSTKFLT`, which is 16 on Linux.
+        sig if sig.raw() <= SIGNAL_SYS.raw() => {
+            let number = if sig.raw() < SIGNAL_CHLD.raw() {
+                sig.raw()
+            } else {
+                sig.raw() + 1
+            };
+            exit::exit_with_code(128 + u32::from(number)); // does not return
+            unreachable!("host exit implementation didn't exit!") // actually unreachable
+        }
+
+        _ => ERRNO_INVAL,
+    }
 }
 
 /// Temporarily yield execution of the calling thread.
 /// Note: This is similar to `sched_yield` in POSIX.
-#[no_mangle]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn sched_yield() -> Errno {
-    // TODO: This is not yet covered in Preview2.
-
+    poll::sched_yield();
     ERRNO_SUCCESS
 }
 
@@ -1838,7 +2157,7 @@ pub unsafe extern "C" fn sched_yield() -> Errno {
 /// This function may execute slowly, so when large mounts of random data are
 /// required, it's advisable to use this function to seed a pseudo-random
 /// number generator, rather than to provide the random data directly.
-#[no_mangle]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn random_get(buf: *mut u8, buf_len: Size) -> Errno {
     if matches!(
         get_allocation_state(),
@@ -1864,15 +2183,40 @@ pub unsafe extern "C" fn random_get(buf: *mut u8, buf_len: Size) -> Errno {
 
 /// Accept a new incoming connection.
 /// Note: This is similar to `accept` in POSIX.
-#[no_mangle]
+#[cfg(sockets)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn sock_accept(fd: Fd, flags: Fdflags, connection: *mut Fd) -> Errno {
-    unreachable!()
+    // `FDFLAGS_NONBLOCK` is the only flag meaningful for a new connection.
+    // Sockets are always blocking for now, so it's accepted but ignored.
+    if flags & !FDFLAGS_NONBLOCK != 0 {
+        return ERRNO_INVAL;
+    }
+
+    State::with_mut(|state| {
+        let mut ds = state.descriptors_mut();
+        ds.check_rights(fd, RIGHTS_SOCK_ACCEPT)?;
+        let (_, inheriting) = ds.get_rights(fd)?;
+        let socket = ds.get_socket(fd)?;
+        let (socket, input, output) = tcp::accept(socket)?;
+        let desc = Descriptor::Streams(Streams {
+            input: Cell::new(Some(input)),
+            output: Cell::new(Some(output)),
+            type_: StreamType::Socket(socket),
+            rights_base: Cell::new(inheriting),
+            rights_inheriting: Cell::new(inheriting),
+        });
+
+        let fd = ds.open(desc)?;
+        *connection = fd;
+        Ok(())
+    })
 }
 
 /// Receive a message from a socket.
 /// Note: This is similar to `recv` in POSIX, though it also supports reading
 /// the data into multiple buffers in the manner of `readv`.
-#[no_mangle]
+#[cfg(sockets)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn sock_recv(
     fd: Fd,
     ri_data_ptr: *const Iovec,
@@ -1881,13 +2225,25 @@ pub unsafe extern "C" fn sock_recv(
     ro_datalen: *mut Size,
     ro_flags: *mut Roflags,
 ) -> Errno {
-    unreachable!()
+    // Peeking and waiting for the whole buffer have no equivalent in streams.
+    if ri_flags != 0 {
+        return ERRNO_NOTSUP;
+    }
+
+    let errno = State::with(|state| state.descriptors().get_socket(fd).map(drop));
+    if errno != ERRNO_SUCCESS {
+        return errno;
+    }
+
+    *ro_flags = 0;
+    fd_read(fd, ri_data_ptr, ri_data_len, ro_datalen)
 }
 
 /// Send a message on a socket.
 /// Note: This is similar to `send` in POSIX, though it also supports writing
 /// the data from multiple buffers in the manner of `writev`.
-#[no_mangle]
+#[cfg(sockets)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn sock_send(
     fd: Fd,
     si_data_ptr: *const Ciovec,
@@ -1895,20 +2251,46 @@ pub unsafe extern "C" fn sock_send(
     si_flags: Siflags,
     so_datalen: *mut Size,
 ) -> Errno {
-    unreachable!()
+    // No send flags are defined.
+    if si_flags != 0 {
+        return ERRNO_INVAL;
+    }
+
+    let errno = State::with(|state| state.descriptors().get_socket(fd).map(drop));
+    if errno != ERRNO_SUCCESS {
+        return errno;
+    }
+
+    fd_write(fd, si_data_ptr, si_data_len, so_datalen)
 }
 
 /// Shut down socket send and receive channels.
 /// Note: This is similar to `shutdown` in POSIX.
-#[no_mangle]
+#[cfg(sockets)]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
 pub unsafe extern "C" fn sock_shutdown(fd: Fd, how: Sdflags) -> Errno {
-    unreachable!()
+    let how = match how {
+        SDFLAGS_RD => tcp::ShutdownType::Receive,
+        SDFLAGS_WR => tcp::ShutdownType::Send,
+        _ if how == SDFLAGS_RD | SDFLAGS_WR => tcp::ShutdownType::Both,
+        _ => return ERRNO_INVAL,
+    };
+
+    State::with(|state| {
+        let ds = state.descriptors();
+        ds.check_rights(fd, RIGHTS_SOCK_SHUTDOWN)?;
+        let socket = ds.get_socket(fd)?;
+        tcp::shutdown(socket, how)?;
+        Ok(())
+    })
 }
 
+#[cfg(filesystem)]
 fn datetime_to_timestamp(datetime: filesystem::Datetime) -> Timestamp {
     u64::from(datetime.nanoseconds).saturating_add(datetime.seconds.saturating_mul(1_000_000_000))
 }
 
+#[cfg(filesystem)]
 fn at_flags_from_lookupflags(flags: Lookupflags) -> filesystem::PathFlags {
     if flags & LOOKUPFLAGS_SYMLINK_FOLLOW == LOOKUPFLAGS_SYMLINK_FOLLOW {
         filesystem::PathFlags::SYMLINK_FOLLOW
@@ -1917,6 +2299,7 @@ fn at_flags_from_lookupflags(flags: Lookupflags) -> filesystem::PathFlags {
     }
 }
 
+#[cfg(filesystem)]
 fn o_flags_from_oflags(flags: Oflags) -> filesystem::OpenFlags {
     let mut o_flags = filesystem::OpenFlags::empty();
     if flags & OFLAGS_CREAT == OFLAGS_CREAT {
@@ -1934,6 +2317,7 @@ fn o_flags_from_oflags(flags: Oflags) -> filesystem::OpenFlags {
     o_flags
 }
 
+#[cfg(filesystem)]
 fn descriptor_flags_from_flags(rights: Rights, fdflags: Fdflags) -> filesystem::DescriptorFlags {
     let mut flags = filesystem::DescriptorFlags::empty();
     if rights & wasi::RIGHTS_FD_READ == wasi::RIGHTS_FD_READ {
@@ -1957,6 +2341,7 @@ fn descriptor_flags_from_flags(rights: Rights, fdflags: Fdflags) -> filesystem::
     flags
 }
 
+#[cfg(not(feature = "proxy"))]
 impl From<filesystem::ErrorCode> for Errno {
     #[inline(never)] // Disable inlining as this is bulky and relatively cold.
     fn from(err: filesystem::ErrorCode) -> Errno {
@@ -2004,6 +2389,7 @@ impl From<filesystem::ErrorCode> for Errno {
     }
 }
 
+#[cfg(not(feature = "proxy"))]
 impl From<filesystem::DescriptorType> for wasi::Filetype {
     fn from(ty: filesystem::DescriptorType) -> wasi::Filetype {
         match ty {
@@ -2013,15 +2399,16 @@ impl From<filesystem::DescriptorType> for wasi::Filetype {
             filesystem::DescriptorType::CharacterDevice => FILETYPE_CHARACTER_DEVICE,
             // preview1 never had a FIFO code.
             filesystem::DescriptorType::Fifo => FILETYPE_UNKNOWN,
-            // TODO: Add a way to disginguish between FILETYPE_SOCKET_STREAM and
-            // FILETYPE_SOCKET_DGRAM.
-            filesystem::DescriptorType::Socket => unreachable!(),
+            // Sockets found in the filesystem are Unix-domain sockets, which
+            // are almost always stream sockets, and there's no way to tell.
+            filesystem::DescriptorType::Socket => FILETYPE_SOCKET_STREAM,
             filesystem::DescriptorType::SymbolicLink => FILETYPE_SYMBOLIC_LINK,
             filesystem::DescriptorType::Unknown => FILETYPE_UNKNOWN,
         }
     }
 }
 
+#[cfg(filesystem)]
 #[repr(C)]
 pub struct File {
     /// The handle to the preview2 descriptor that this file is referencing.
@@ -2034,14 +2421,17 @@ pub struct File {
     append: bool,
 }
 
-const PAGE_SIZE: usize = 65536;
+pub(crate) const PAGE_SIZE: usize = 65536;
 
-/// The maximum path length. WASI doesn't explicitly guarantee this, but all
-/// popular OS's have a `PATH_MAX` of at most 4096, so that's enough for this
-/// polyfill.
+/// The size of the buffer for directory entry names. WASI doesn't explicitly
+/// limit their length, but all popular OS's have a `PATH_MAX` of at most 4096,
+/// so longer names are rare enough to be read into the import allocator's
+/// overflow buffer instead.
+#[cfg(filesystem)]
 const PATH_MAX: usize = 4096;
 
 /// Maximum number of bytes to cache for a `wasi::Dirent` plus its path name.
+#[cfg(filesystem)]
 const DIRENT_CACHE: usize = 256;
 
 /// A canary value to detect memory corruption within `State`.
@@ -2056,6 +2446,7 @@ struct State {
     /// Used to coordinate allocations of `cabi_import_realloc`
     import_alloc: ImportAlloc,
 
+    #[cfg(not(feature = "proxy"))]
     /// Storage of mapping from preview1 file descriptors to preview2 file
     /// descriptors.
     ///
@@ -2063,7 +2454,8 @@ struct State {
     /// lazy initialization happens.
     descriptors: RefCell<Option<Descriptors>>,
 
-    /// Auxiliary storage to handle the `path_readlink` function.
+    #[cfg(filesystem)]
+    /// Auxiliary storage for the names of entries read by `fd_readdir`.
     path_buf: UnsafeCell<MaybeUninit<[u8; PATH_MAX]>>,
 
     /// Long-lived bump allocated memory arena.
@@ -2074,18 +2466,22 @@ struct State {
     /// which need to be long-lived, by using `import_alloc.with_arena`.
     long_lived_arena: BumpArena,
 
+    #[cfg(not(feature = "proxy"))]
     /// Arguments. Initialized lazily. Access with `State::get_args` to take care of
     /// initialization.
     args: Cell<Option<&'static [WasmStr]>>,
 
+    #[cfg(not(feature = "proxy"))]
     /// Environment variables. Initialized lazily. Access with `State::get_environment`
     /// to take care of initialization.
     env_vars: Cell<Option<&'static [StrTuple]>>,
 
+    #[cfg(filesystem)]
     /// Cache for the `fd_readdir` call for a final `wasi::Dirent` plus path
     /// name that didn't fit into the caller's buffer.
     dirent_cache: DirentCache,
 
+    #[cfg(filesystem)]
     /// The string `..` for use by the directory iterator.
     dotdot: [UnsafeCell<u8>; 2],
 
@@ -2094,6 +2490,7 @@ struct State {
     magic2: u32,
 }
 
+#[cfg(filesystem)]
 struct DirentCache {
     stream: Cell<Option<DirectoryEntryStream>>,
     for_fd: Cell<wasi::Fd>,
@@ -2102,32 +2499,38 @@ struct DirentCache {
     path_data: UnsafeCell<MaybeUninit<[u8; DIRENT_CACHE]>>,
 }
 
+#[cfg(filesystem)]
 struct DirectoryEntryStream(filesystem::DirectoryEntryStream);
 
+#[cfg(filesystem)]
 impl Drop for DirectoryEntryStream {
     fn drop(&mut self) {
         filesystem::drop_directory_entry_stream(self.0);
     }
 }
 
+#[cfg(not(feature = "proxy"))]
 #[repr(C)]
 pub struct WasmStr {
     ptr: *const u8,
     len: usize,
 }
 
+#[cfg(not(feature = "proxy"))]
 #[repr(C)]
 pub struct WasmStrList {
     base: *const WasmStr,
     len: usize,
 }
 
+#[cfg(not(feature = "proxy"))]
 #[repr(C)]
 pub struct StrTuple {
     key: WasmStr,
     value: WasmStr,
 }
 
+#[cfg(not(feature = "proxy"))]
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub struct StrTupleList {
@@ -2135,15 +2538,39 @@ pub struct StrTupleList {
     len: usize,
 }
 
+/// The `readiness` record of the `poll` interface.
+#[cfg(not(feature = "proxy"))]
+#[repr(C)]
+pub struct Readiness {
+    ready: u8,
+    hangup: u8,
+    nbytes: u64,
+}
+
+#[cfg(not(feature = "proxy"))]
+#[repr(C)]
+pub struct ReadinessList {
+    base: *const Readiness,
+    len: usize,
+}
+
 const fn bump_arena_size() -> usize {
     // The total size of the struct should be a page, so start there
     let mut start = PAGE_SIZE;
 
     // Remove the big chunks of the struct, the `path_buf` and `descriptors`
     // fields.
-    start -= PATH_MAX;
-    start -= size_of::<Descriptors>();
-    start -= size_of::<DirentCache>();
+    #[cfg(not(feature = "proxy"))]
+    {
+        start -= size_of::<Descriptors>();
+    }
+    #[cfg(filesystem)]
+    {
+        start -= PATH_MAX;
+        start -= size_of::<DirentCache>();
+    }
+    start -= size_of::<Spill>();
+    start -= size_of::<Overflow>();
 
     // Remove miscellaneous metadata also stored in state.
     start -= 16 * size_of::<usize>();
@@ -2155,10 +2582,16 @@ const fn bump_arena_size() -> usize {
 // Statically assert that the `State` structure is the size of a wasm page. This
 // mostly guarantees that it's not larger than one page which is relied upon
 // below.
+#[cfg(not(feature = "proxy"))]
 const _: () = {
     let _size_assert: [(); PAGE_SIZE] = [(); size_of::<RefCell<State>>()];
 };
 
+// The proxy adapter's `State` leaves out the fields which the page-sized
+// calculation above accounts for, so just check that it fits in a page.
+#[cfg(feature = "proxy")]
+const _: () = core::assert!(size_of::<RefCell<State>>() <= PAGE_SIZE);
+
 #[allow(unused)]
 #[repr(i32)]
 enum AllocationState {
@@ -2175,10 +2608,39 @@ extern "C" {
     fn set_state_ptr(state: *const RefCell<State>);
     fn get_allocation_state() -> AllocationState;
     fn set_allocation_state(state: AllocationState);
+    #[cfg(not(feature = "proxy"))]
     fn get_stderr_stream() -> Fd;
+    #[cfg(not(feature = "proxy"))]
     fn set_stderr_stream(fd: Fd);
 }
 
+/// Return whether `trace!` can log, which needs the stderr stream, so it's
+/// only once the descriptors exist. Logging doesn't initialize them itself, as
+/// that would change when the adapter calls its imports, so calls made before
+/// then aren't logged.
+#[cfg(all(feature = "trace", not(feature = "proxy")))]
+fn trace_ready() -> bool {
+    let state = unsafe { get_state_ptr() };
+    if state.is_null() {
+        return false;
+    }
+    match unsafe { &*state }.try_borrow() {
+        Ok(state) => matches!(state.descriptors.try_borrow().as_deref(), Ok(Some(_))),
+        Err(_) => false,
+    }
+}
+
+/// Return whether `trace!` can log. The proxy world logs to the console, which
+/// is there from the start, so it's only calls made before the state may be
+/// allocated which aren't logged.
+#[cfg(all(feature = "trace", feature = "proxy"))]
+fn trace_ready() -> bool {
+    matches!(
+        unsafe { get_allocation_state() },
+        AllocationState::StackAllocated | AllocationState::StateAllocated
+    )
+}
+
 impl State {
     fn with(f: impl FnOnce(&State) -> Result<(), Errno>) -> Errno {
         let ptr = State::ptr();
@@ -2217,16 +2679,6 @@ impl State {
 
     #[cold]
     fn new() -> &'static RefCell<State> {
-        #[link(wasm_import_module = "__main_module__")]
-        extern "C" {
-            fn cabi_realloc(
-                old_ptr: *mut u8,
-                old_len: usize,
-                align: usize,
-                new_len: usize,
-            ) -> *mut u8;
-        }
-
         assert!(matches!(
             unsafe { get_allocation_state() },
             AllocationState::StackAllocated
@@ -2250,11 +2702,16 @@ impl State {
                 magic1: MAGIC,
                 magic2: MAGIC,
                 import_alloc: ImportAlloc::new(),
+                #[cfg(not(feature = "proxy"))]
                 descriptors: RefCell::new(None),
+                #[cfg(filesystem)]
                 path_buf: UnsafeCell::new(MaybeUninit::uninit()),
                 long_lived_arena: BumpArena::new(),
+                #[cfg(not(feature = "proxy"))]
                 args: Cell::new(None),
+                #[cfg(not(feature = "proxy"))]
                 env_vars: Cell::new(None),
+                #[cfg(filesystem)]
                 dirent_cache: DirentCache {
                     stream: Cell::new(None),
                     for_fd: Cell::new(0),
@@ -2267,12 +2724,14 @@ impl State {
                     }),
                     path_data: UnsafeCell::new(MaybeUninit::uninit()),
                 },
+                #[cfg(filesystem)]
                 dotdot: [UnsafeCell::new(b'.'), UnsafeCell::new(b'.')],
             }));
             &*ret
         }
     }
 
+    #[cfg(not(feature = "proxy"))]
     /// Accessor for the descriptors member that ensures it is properly initialized
     fn descriptors<'a>(&'a self) -> impl Deref<Target = Descriptors> + 'a {
         let mut d = self
@@ -2280,11 +2739,16 @@ impl State {
             .try_borrow_mut()
             .unwrap_or_else(|_| unreachable!());
         if d.is_none() {
-            *d = Some(Descriptors::new(&self.import_alloc, &self.long_lived_arena));
+            *d = Some(Descriptors::new(
+                &self.import_alloc,
+                &self.long_lived_arena,
+                self.get_pwd(),
+            ));
         }
         RefMut::map(d, |d| d.as_mut().unwrap_or_else(|| unreachable!()))
     }
 
+    #[cfg(not(feature = "proxy"))]
     /// Mut accessor for the descriptors member that ensures it is properly initialized
     fn descriptors_mut<'a>(&'a mut self) -> impl DerefMut + Deref<Target = Descriptors> + 'a {
         let mut d = self
@@ -2292,11 +2756,31 @@ impl State {
             .try_borrow_mut()
             .unwrap_or_else(|_| unreachable!());
         if d.is_none() {
-            *d = Some(Descriptors::new(&self.import_alloc, &self.long_lived_arena));
+            *d = Some(Descriptors::new(
+                &self.import_alloc,
+                &self.long_lived_arena,
+                self.get_pwd(),
+            ));
         }
         RefMut::map(d, |d| d.as_mut().unwrap_or_else(|| unreachable!()))
     }
 
+    #[cfg(not(feature = "proxy"))]
+    /// The `PWD` environment variable, which names the current working
+    /// directory. It's only used to resolve paths, so without the filesystem
+    /// this doesn't bother fetching the environment.
+    fn get_pwd(&self) -> Option<&[u8]> {
+        if !cfg!(filesystem) {
+            return None;
+        }
+        self.get_environment().iter().find_map(|var| unsafe {
+            let key = slice::from_raw_parts(var.key.ptr, var.key.len);
+            matches!(key, [b'P', b'W', b'D'])
+                .then(|| slice::from_raw_parts(var.value.ptr, var.value.len))
+        })
+    }
+
+    #[cfg(not(feature = "proxy"))]
     fn get_environment(&self) -> &[StrTuple] {
         if self.env_vars.get().is_none() {
             #[link(wasm_import_module = "environment")]
@@ -2321,6 +2805,7 @@ impl State {
         self.env_vars.get().trapping_unwrap()
     }
 
+    #[cfg(not(feature = "proxy"))]
     fn get_args(&self) -> &[WasmStr] {
         if self.args.get().is_none() {
             #[link(wasm_import_module = "environment")]
@@ -4,11 +4,27 @@
 //! literals. Replace the standard assert macros with simpler implementations.
 #[allow(dead_code)]
 #[doc(hidden)]
+#[cfg(not(feature = "proxy"))]
 pub fn print(message: &[u8]) {
     let _ = unsafe { crate::bindings::streams::write(crate::get_stderr_stream(), message) };
 }
 
+/// The proxy world has no stderr, so diagnostics go to the console instead.
+#[allow(dead_code)]
+#[doc(hidden)]
+#[cfg(feature = "proxy")]
+pub fn print(message: &[u8]) {
+    crate::bindings::console::log(
+        crate::bindings::console::Level::Error,
+        &byte_array::str!("stderr"),
+        message,
+    );
+}
+
 /// A minimal `eprint` for debugging.
+///
+/// Arguments are formatted by `byte_array::format!`, which supports `{}` and
+/// `{:x}` for unsigned integers and `{:?}` for byte slices.
 #[allow(unused_macros)]
 macro_rules! eprint {
     ($arg:tt) => {{
@@ -17,6 +33,10 @@ macro_rules! eprint {
         let message = byte_array::str!($arg);
         $crate::macros::print(&message);
     }};
+
+    ($fmt:tt, $($args:expr),+ $(,)?) => {
+        byte_array::format!($crate::macros::print, $fmt, $($args),+)
+    };
 }
 
 /// A minimal `eprintln` for debugging.
@@ -28,31 +48,98 @@ macro_rules! eprintln {
         let message = byte_array::str_nl!($arg);
         $crate::macros::print(&message);
     }};
+
+    ($fmt:tt, $($args:expr),+ $(,)?) => {{
+        eprint!($fmt, $($args),+);
+        eprint!("\n");
+    }};
 }
 
-pub(crate) fn eprint_u32(x: u32) {
-    if x == 0 {
-        eprint!("0");
-    } else {
-        eprint_u32_impl(x)
-    }
+/// Log a call to an exported preview1 function, along with some of its
+/// arguments, if `trace_ready`, and return whether it was logged, so that the
+/// errno it returns is only logged if the call was. Used by the shims in
+/// `trace.rs`.
+#[cfg(feature = "trace")]
+macro_rules! trace {
+    ($name:tt $(, $arg:tt = $value:expr)* $(,)?) => {{
+        let ready = crate::trace_ready();
+        if ready {
+            eprint!($name);
+            $(
+                eprint!(" ");
+                eprint!($arg);
+                eprint!("=");
+                crate::macros::TraceArg::eprint($value);
+            )*
+            eprint!("\n");
+        }
+        ready
+    }};
+}
+
+/// An argument which can be logged by `trace!`.
+#[cfg(feature = "trace")]
+pub(crate) trait TraceArg {
+    fn eprint(self);
+}
+
+#[cfg(feature = "trace")]
+macro_rules! trace_arg_unsigned {
+    ($($ty:ty)*) => {$(
+        impl TraceArg for $ty {
+            fn eprint(self) {
+                eprint!("{}", self)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "trace")]
+trace_arg_unsigned!(u8 u16 u32 u64 usize);
+
+/// Enumerations are logged as their raw value.
+#[cfg(feature = "trace")]
+macro_rules! trace_arg_enum {
+    ($($ty:ty)*) => {$(
+        impl TraceArg for $ty {
+            fn eprint(self) {
+                eprint!("{}", self.raw())
+            }
+        }
+    )*};
+}
 
-    fn eprint_u32_impl(x: u32) {
-        if x != 0 {
-            eprint_u32_impl(x / 10);
+#[cfg(feature = "trace")]
+trace_arg_enum!(wasi::Advice wasi::Clockid wasi::Signal wasi::Whence);
 
-            let digit = [b'0' + ((x % 10) as u8)];
-            crate::macros::print(&digit);
+#[cfg(feature = "trace")]
+impl TraceArg for i64 {
+    fn eprint(self) {
+        if self < 0 {
+            eprint!("-");
         }
+        eprint!("{}", self.unsigned_abs())
     }
 }
 
+/// Paths are logged as-is, between quotes.
+#[cfg(feature = "trace")]
+impl TraceArg for &[u8] {
+    fn eprint(self) {
+        eprint!("{:?}", self)
+    }
+}
+
+/// Log the errno returned by an exported preview1 function.
+#[cfg(feature = "trace")]
+pub(crate) fn trace_errno(errno: wasi::Errno) {
+    eprintln!("  -> errno={}", errno.raw());
+}
+
 /// A minimal `unreachable`.
 macro_rules! unreachable {
     () => {{
-        eprint!("unreachable executed at adapter line ");
-        crate::macros::eprint_u32(line!());
-        eprint!("\n");
+        eprintln!("unreachable executed at adapter line {}", line!());
         #[cfg(target_arch = "wasm32")]
         core::arch::wasm32::unreachable();
         // This is here to keep rust-analyzer happy when building for native:
@@ -60,11 +147,9 @@ macro_rules! unreachable {
         std::process::abort();
     }};
 
-    ($arg:tt) => {{
-        eprint!("unreachable executed at adapter line ");
-        crate::macros::eprint_u32(line!());
-        eprint!(": ");
-        eprintln!($arg);
+    ($($arg:tt)+) => {{
+        eprint!("unreachable executed at adapter line {}: ", line!());
+        eprintln!($($arg)+);
         eprint!("\n");
         #[cfg(target_arch = "wasm32")]
         core::arch::wasm32::unreachable();
@@ -0,0 +1,284 @@
+//! The preview1 functions for the interfaces which this build of the adapter
+//! leaves out, when it's built with the `no-filesystem` or `no-sockets` features.
+//!
+//! These return `ERRNO_NOSYS` without touching any state, so that the adapter
+//! doesn't import anything for them. The exception is that there are never
+//! any preopened directories, which wasi-libc expects to learn from
+//! `ERRNO_BADF`.
+
+use wasi::*;
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_advise(
+    fd: Fd,
+    offset: Filesize,
+    len: Filesize,
+    advice: Advice,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_allocate(fd: Fd, offset: Filesize, len: Filesize) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_datasync(fd: Fd) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_fdstat_set_flags(fd: Fd, flags: Fdflags) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_filestat_set_size(fd: Fd, size: Filesize) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_filestat_set_times(
+    fd: Fd,
+    atim: Timestamp,
+    mtim: Timestamp,
+    fst_flags: Fstflags,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_pread(
+    fd: Fd,
+    iovs_ptr: *const Iovec,
+    iovs_len: usize,
+    offset: Filesize,
+    nread: *mut Size,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+/// There are no preopened directories.
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_prestat_get(fd: Fd, buf: *mut Prestat) -> Errno {
+    ERRNO_BADF
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_prestat_dir_name(fd: Fd, path: *mut u8, path_len: Size) -> Errno {
+    ERRNO_BADF
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_pwrite(
+    fd: Fd,
+    iovs_ptr: *const Ciovec,
+    iovs_len: usize,
+    offset: Filesize,
+    nwritten: *mut Size,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_readdir(
+    fd: Fd,
+    buf: *mut u8,
+    buf_len: Size,
+    cookie: Dircookie,
+    bufused: *mut Size,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_seek(
+    fd: Fd,
+    offset: Filedelta,
+    whence: Whence,
+    newoffset: *mut Filesize,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_sync(fd: Fd) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_tell(fd: Fd, offset: *mut Filesize) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_create_directory(
+    fd: Fd,
+    path_ptr: *const u8,
+    path_len: usize,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_filestat_get(
+    fd: Fd,
+    flags: Lookupflags,
+    path_ptr: *const u8,
+    path_len: usize,
+    buf: *mut Filestat,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_filestat_set_times(
+    fd: Fd,
+    flags: Lookupflags,
+    path_ptr: *const u8,
+    path_len: usize,
+    atim: Timestamp,
+    mtim: Timestamp,
+    fst_flags: Fstflags,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_link(
+    old_fd: Fd,
+    old_flags: Lookupflags,
+    old_path_ptr: *const u8,
+    old_path_len: usize,
+    new_fd: Fd,
+    new_path_ptr: *const u8,
+    new_path_len: usize,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_open(
+    fd: Fd,
+    dirflags: Lookupflags,
+    path_ptr: *const u8,
+    path_len: usize,
+    oflags: Oflags,
+    fs_rights_base: Rights,
+    fs_rights_inheriting: Rights,
+    fdflags: Fdflags,
+    opened_fd: *mut Fd,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_readlink(
+    fd: Fd,
+    path_ptr: *const u8,
+    path_len: usize,
+    buf: *mut u8,
+    buf_len: Size,
+    bufused: *mut Size,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_remove_directory(
+    fd: Fd,
+    path_ptr: *const u8,
+    path_len: usize,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_rename(
+    old_fd: Fd,
+    old_path_ptr: *const u8,
+    old_path_len: usize,
+    new_fd: Fd,
+    new_path_ptr: *const u8,
+    new_path_len: usize,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_symlink(
+    old_path_ptr: *const u8,
+    old_path_len: usize,
+    fd: Fd,
+    new_path_ptr: *const u8,
+    new_path_len: usize,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(filesystem))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_unlink_file(fd: Fd, path_ptr: *const u8, path_len: usize) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(sockets))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn sock_accept(fd: Fd, flags: Fdflags, connection: *mut Fd) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(sockets))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn sock_recv(
+    fd: Fd,
+    ri_data_ptr: *const Iovec,
+    ri_data_len: usize,
+    ri_flags: Riflags,
+    ro_datalen: *mut Size,
+    ro_flags: *mut Roflags,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(sockets))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn sock_send(
+    fd: Fd,
+    si_data_ptr: *const Ciovec,
+    si_data_len: usize,
+    si_flags: Siflags,
+    so_datalen: *mut Size,
+) -> Errno {
+    ERRNO_NOSYS
+}
+
+#[cfg(not(sockets))]
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn sock_shutdown(fd: Fd, how: Sdflags) -> Errno {
+    ERRNO_NOSYS
+}
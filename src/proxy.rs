@@ -0,0 +1,487 @@
+//! The preview1 functions for the `proxy` world.
+//!
+//! The proxy world has no filesystem, sockets, clocks, environment or exit
+//! interfaces, so almost everything here reports an error. Only the stdio
+//! file descriptors exist: stdin is always empty, and writes to stdout and
+//! stderr are sent to the console, one log message per write.
+//!
+//! `random_get` and `sched_yield` are shared with the other worlds.
+
+use crate::bindings::console;
+use wasi::*;
+
+/// Return `ERRNO_BADF` for anything but the stdio file descriptors.
+fn check_stdio(fd: Fd) -> Result<(), Errno> {
+    if fd <= 2 {
+        Ok(())
+    } else {
+        Err(ERRNO_BADF)
+    }
+}
+
+/// Writes to stdout and stderr go to the console, which stands in for a
+/// terminal, so they're character devices. Stdin is always empty, and isn't
+/// anything in particular.
+fn stdio_filetype(fd: Fd) -> Filetype {
+    match fd {
+        1 | 2 => FILETYPE_CHARACTER_DEVICE,
+        _ => FILETYPE_UNKNOWN,
+    }
+}
+
+/// Return `ERRNO_NOTSUP` for the stdio file descriptors, which don't support
+/// the operation, and `ERRNO_BADF` for anything else.
+fn unsupported(fd: Fd) -> Errno {
+    match check_stdio(fd) {
+        Ok(()) => ERRNO_NOTSUP,
+        Err(e) => e,
+    }
+}
+
+/// There are no command-line arguments.
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn args_get(argv: *mut *mut u8, argv_buf: *mut u8) -> Errno {
+    ERRNO_SUCCESS
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn args_sizes_get(argc: *mut Size, argv_buf_size: *mut Size) -> Errno {
+    *argc = 0;
+    *argv_buf_size = 0;
+    ERRNO_SUCCESS
+}
+
+/// There are no environment variables.
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn environ_get(environ: *mut *mut u8, environ_buf: *mut u8) -> Errno {
+    ERRNO_SUCCESS
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn environ_sizes_get(
+    environc: *mut Size,
+    environ_buf_size: *mut Size,
+) -> Errno {
+    *environc = 0;
+    *environ_buf_size = 0;
+    ERRNO_SUCCESS
+}
+
+/// There are no clocks.
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub extern "C" fn clock_res_get(id: Clockid, resolution: &mut Timestamp) -> Errno {
+    ERRNO_NOTSUP
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn clock_time_get(
+    id: Clockid,
+    _precision: Timestamp,
+    time: &mut Timestamp,
+) -> Errno {
+    ERRNO_NOTSUP
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_advise(
+    fd: Fd,
+    offset: Filesize,
+    len: Filesize,
+    advice: Advice,
+) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_allocate(fd: Fd, offset: Filesize, len: Filesize) -> Errno {
+    unsupported(fd)
+}
+
+/// The stdio file descriptors don't hold any resources, so closing them
+/// does nothing.
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_close(fd: Fd) -> Errno {
+    match check_stdio(fd) {
+        Ok(()) => ERRNO_SUCCESS,
+        Err(e) => e,
+    }
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_datasync(fd: Fd) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_fdstat_get(fd: Fd, stat: *mut Fdstat) -> Errno {
+    let fs_rights_base = match fd {
+        0 => RIGHTS_FD_READ | RIGHTS_POLL_FD_READWRITE,
+        1 | 2 => RIGHTS_FD_WRITE | RIGHTS_POLL_FD_READWRITE,
+        _ => return ERRNO_BADF,
+    };
+    stat.write(Fdstat {
+        fs_filetype: stdio_filetype(fd),
+        fs_flags: 0,
+        fs_rights_base,
+        fs_rights_inheriting: 0,
+    });
+    ERRNO_SUCCESS
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_fdstat_set_flags(fd: Fd, flags: Fdflags) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_fdstat_set_rights(
+    fd: Fd,
+    fs_rights_base: Rights,
+    fs_rights_inheriting: Rights,
+) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_filestat_get(fd: Fd, buf: *mut Filestat) -> Errno {
+    if let Err(e) = check_stdio(fd) {
+        return e;
+    }
+    buf.write(Filestat {
+        dev: 0,
+        ino: 0,
+        filetype: stdio_filetype(fd),
+        nlink: 0,
+        size: 0,
+        atim: 0,
+        mtim: 0,
+        ctim: 0,
+    });
+    ERRNO_SUCCESS
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_filestat_set_size(fd: Fd, size: Filesize) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_filestat_set_times(
+    fd: Fd,
+    atim: Timestamp,
+    mtim: Timestamp,
+    fst_flags: Fstflags,
+) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_pread(
+    fd: Fd,
+    iovs_ptr: *const Iovec,
+    iovs_len: usize,
+    offset: Filesize,
+    nread: *mut Size,
+) -> Errno {
+    unsupported(fd)
+}
+
+/// There are no preopened directories.
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_prestat_get(fd: Fd, buf: *mut Prestat) -> Errno {
+    ERRNO_BADF
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_prestat_dir_name(fd: Fd, path: *mut u8, path_len: Size) -> Errno {
+    ERRNO_BADF
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_pwrite(
+    fd: Fd,
+    iovs_ptr: *const Ciovec,
+    iovs_len: usize,
+    offset: Filesize,
+    nwritten: *mut Size,
+) -> Errno {
+    unsupported(fd)
+}
+
+/// Stdin is always at end-of-file.
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_read(
+    fd: Fd,
+    iovs_ptr: *const Iovec,
+    iovs_len: usize,
+    nread: *mut Size,
+) -> Errno {
+    match fd {
+        0 => {
+            *nread = 0;
+            ERRNO_SUCCESS
+        }
+        _ => unsupported(fd),
+    }
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_readdir(
+    fd: Fd,
+    buf: *mut u8,
+    buf_len: Size,
+    cookie: Dircookie,
+    bufused: *mut Size,
+) -> Errno {
+    match check_stdio(fd) {
+        Ok(()) => ERRNO_NOTDIR,
+        Err(e) => e,
+    }
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_renumber(fd: Fd, to: Fd) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_seek(
+    fd: Fd,
+    offset: Filedelta,
+    whence: Whence,
+    newoffset: *mut Filesize,
+) -> Errno {
+    match check_stdio(fd) {
+        Ok(()) => ERRNO_SPIPE,
+        Err(e) => e,
+    }
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_sync(fd: Fd) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_tell(fd: Fd, offset: *mut Filesize) -> Errno {
+    match check_stdio(fd) {
+        Ok(()) => ERRNO_SPIPE,
+        Err(e) => e,
+    }
+}
+
+/// Send writes to stdout and stderr to the console, at the info and error
+/// levels respectively.
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn fd_write(
+    fd: Fd,
+    mut iovs_ptr: *const Ciovec,
+    mut iovs_len: usize,
+    nwritten: *mut Size,
+) -> Errno {
+    let (level, context) = match fd {
+        1 => (console::Level::Info, byte_array::str!("stdout")),
+        2 => (console::Level::Error, byte_array::str!("stderr")),
+        _ => return unsupported(fd),
+    };
+
+    // Advance to the first non-empty buffer.
+    while iovs_len != 0 && (*iovs_ptr).buf_len == 0 {
+        iovs_ptr = iovs_ptr.add(1);
+        iovs_len -= 1;
+    }
+    if iovs_len == 0 {
+        *nwritten = 0;
+        return ERRNO_SUCCESS;
+    }
+
+    let len = (*iovs_ptr).buf_len;
+    let mut message = core::slice::from_raw_parts((*iovs_ptr).buf, len);
+
+    // Each message is a line of its own, so drop a trailing newline.
+    if let [rest @ .., b'\n'] = message {
+        message = rest;
+    }
+    console::log(level, &context, message);
+
+    *nwritten = len;
+    ERRNO_SUCCESS
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_create_directory(
+    fd: Fd,
+    path_ptr: *const u8,
+    path_len: usize,
+) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_filestat_get(
+    fd: Fd,
+    flags: Lookupflags,
+    path_ptr: *const u8,
+    path_len: usize,
+    buf: *mut Filestat,
+) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_filestat_set_times(
+    fd: Fd,
+    flags: Lookupflags,
+    path_ptr: *const u8,
+    path_len: usize,
+    atim: Timestamp,
+    mtim: Timestamp,
+    fst_flags: Fstflags,
+) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_link(
+    old_fd: Fd,
+    old_flags: Lookupflags,
+    old_path_ptr: *const u8,
+    old_path_len: usize,
+    new_fd: Fd,
+    new_path_ptr: *const u8,
+    new_path_len: usize,
+) -> Errno {
+    unsupported(old_fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_open(
+    fd: Fd,
+    dirflags: Lookupflags,
+    path_ptr: *const u8,
+    path_len: usize,
+    oflags: Oflags,
+    fs_rights_base: Rights,
+    fs_rights_inheriting: Rights,
+    fdflags: Fdflags,
+    opened_fd: *mut Fd,
+) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_readlink(
+    fd: Fd,
+    path_ptr: *const u8,
+    path_len: usize,
+    buf: *mut u8,
+    buf_len: Size,
+    bufused: *mut Size,
+) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_remove_directory(
+    fd: Fd,
+    path_ptr: *const u8,
+    path_len: usize,
+) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_rename(
+    old_fd: Fd,
+    old_path_ptr: *const u8,
+    old_path_len: usize,
+    new_fd: Fd,
+    new_path_ptr: *const u8,
+    new_path_len: usize,
+) -> Errno {
+    unsupported(old_fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_symlink(
+    old_path_ptr: *const u8,
+    old_path_len: usize,
+    fd: Fd,
+    new_path_ptr: *const u8,
+    new_path_len: usize,
+) -> Errno {
+    unsupported(fd)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn path_unlink_file(fd: Fd, path_ptr: *const u8, path_len: usize) -> Errno {
+    unsupported(fd)
+}
+
+/// There are no clocks to wait on, and the stdio streams don't support
+/// polling.
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn poll_oneoff(
+    r#in: *const Subscription,
+    out: *mut Event,
+    nsubscriptions: Size,
+    nevents: *mut Size,
+) -> Errno {
+    ERRNO_NOTSUP
+}
+
+/// There's no way to exit early in the proxy world, so this traps.
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn proc_exit(rval: Exitcode) -> ! {
+    unreachable!("proc_exit({}) isn't supported in the proxy world", rval)
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn proc_raise(sig: Signal) -> Errno {
+    ERRNO_NOTSUP
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn sock_accept(fd: Fd, flags: Fdflags, connection: *mut Fd) -> Errno {
+    match check_stdio(fd) {
+        Ok(()) => ERRNO_NOTSOCK,
+        Err(e) => e,
+    }
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn sock_recv(
+    fd: Fd,
+    ri_data_ptr: *const Iovec,
+    ri_data_len: usize,
+    ri_flags: Riflags,
+    ro_datalen: *mut Size,
+    ro_flags: *mut Roflags,
+) -> Errno {
+    match check_stdio(fd) {
+        Ok(()) => ERRNO_NOTSOCK,
+        Err(e) => e,
+    }
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn sock_send(
+    fd: Fd,
+    si_data_ptr: *const Ciovec,
+    si_data_len: usize,
+    si_flags: Siflags,
+    so_datalen: *mut Size,
+) -> Errno {
+    match check_stdio(fd) {
+        Ok(()) => ERRNO_NOTSOCK,
+        Err(e) => e,
+    }
+}
+
+#[cfg_attr(not(feature = "trace"), no_mangle)]
+pub unsafe extern "C" fn sock_shutdown(fd: Fd, how: Sdflags) -> Errno {
+    match check_stdio(fd) {
+        Ok(()) => ERRNO_NOTSOCK,
+        Err(e) => e,
+    }
+}
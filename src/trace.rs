@@ -0,0 +1,269 @@
+//! The exports of the `trace` feature, which log each preview1 call and the
+//! errno it returns to stderr.
+//!
+//! With this feature the preview1 functions aren't exported themselves.
+//! Instead each is wrapped by a shim here, exported under its name, so that
+//! every call is logged once, however the function returns, and calls the
+//! adapter makes from one preview1 function to another aren't logged at all.
+
+use wasi::*;
+
+// Where each group of preview1 functions is implemented in this build.
+use crate as shared;
+#[cfg(not(feature = "proxy"))]
+use crate as world;
+#[cfg(filesystem)]
+use crate as fs;
+#[cfg(sockets)]
+use crate as sock;
+#[cfg(all(not(filesystem), not(feature = "proxy")))]
+use crate::nosys as fs;
+#[cfg(all(not(sockets), not(feature = "proxy")))]
+use crate::nosys as sock;
+#[cfg(feature = "proxy")]
+use crate::proxy as world;
+#[cfg(feature = "proxy")]
+use crate::proxy as fs;
+#[cfg(feature = "proxy")]
+use crate::proxy as sock;
+
+/// Define a shim for each of the given preview1 functions, which calls the
+/// function at the given path, logging the call with the given arguments to
+/// `trace!`, and the errno it returns.
+macro_rules! traced {
+    ($(
+        $imp:ident::$name:ident($($arg:ident: $ty:ty),* $(,)?) => ($($trace:tt)*);
+    )*) => {$(
+        #[export_name = stringify!($name)]
+        pub unsafe extern "C" fn $name($($arg: $ty),*) -> Errno {
+            let traced = trace!($($trace)*);
+            let errno = $imp::$name($($arg),*);
+            if traced {
+                crate::macros::trace_errno(errno);
+            }
+            errno
+        }
+    )*};
+}
+
+traced! {
+    world::args_get(argv: *mut *mut u8, argv_buf: *mut u8) => ("args_get");
+    world::args_sizes_get(argc: *mut Size, argv_buf_size: *mut Size) => ("args_sizes_get");
+    world::environ_get(environ: *mut *mut u8, environ_buf: *mut u8) => ("environ_get");
+    world::environ_sizes_get(environc: *mut Size, environ_buf_size: *mut Size)
+        => ("environ_sizes_get");
+    world::clock_res_get(id: Clockid, resolution: &mut Timestamp) => ("clock_res_get", "id" = id);
+    world::clock_time_get(id: Clockid, precision: Timestamp, time: &mut Timestamp)
+        => ("clock_time_get", "id" = id);
+    fs::fd_advise(fd: Fd, offset: Filesize, len: Filesize, advice: Advice)
+        => ("fd_advise", "fd" = fd, "offset" = offset, "len" = len, "advice" = advice);
+    fs::fd_allocate(fd: Fd, offset: Filesize, len: Filesize)
+        => ("fd_allocate", "fd" = fd, "offset" = offset, "len" = len);
+    world::fd_close(fd: Fd) => ("fd_close", "fd" = fd);
+    fs::fd_datasync(fd: Fd) => ("fd_datasync", "fd" = fd);
+    world::fd_fdstat_get(fd: Fd, stat: *mut Fdstat) => ("fd_fdstat_get", "fd" = fd);
+    fs::fd_fdstat_set_flags(fd: Fd, flags: Fdflags)
+        => ("fd_fdstat_set_flags", "fd" = fd, "flags" = flags);
+    world::fd_fdstat_set_rights(fd: Fd, fs_rights_base: Rights, fs_rights_inheriting: Rights)
+        => (
+            "fd_fdstat_set_rights",
+            "fd" = fd,
+            "fs_rights_base" = fs_rights_base,
+            "fs_rights_inheriting" = fs_rights_inheriting,
+        );
+    world::fd_filestat_get(fd: Fd, buf: *mut Filestat) => ("fd_filestat_get", "fd" = fd);
+    fs::fd_filestat_set_size(fd: Fd, size: Filesize)
+        => ("fd_filestat_set_size", "fd" = fd, "size" = size);
+    fs::fd_filestat_set_times(fd: Fd, atim: Timestamp, mtim: Timestamp, fst_flags: Fstflags)
+        => (
+            "fd_filestat_set_times",
+            "fd" = fd,
+            "atim" = atim,
+            "mtim" = mtim,
+            "fst_flags" = fst_flags,
+        );
+    fs::fd_pread(
+        fd: Fd,
+        iovs_ptr: *const Iovec,
+        iovs_len: usize,
+        offset: Filesize,
+        nread: *mut Size,
+    ) => ("fd_pread", "fd" = fd, "iovs_len" = iovs_len, "offset" = offset);
+    fs::fd_prestat_get(fd: Fd, buf: *mut Prestat) => ("fd_prestat_get", "fd" = fd);
+    fs::fd_prestat_dir_name(fd: Fd, path: *mut u8, path_len: Size)
+        => ("fd_prestat_dir_name", "fd" = fd, "path_len" = path_len);
+    fs::fd_pwrite(
+        fd: Fd,
+        iovs_ptr: *const Ciovec,
+        iovs_len: usize,
+        offset: Filesize,
+        nwritten: *mut Size,
+    ) => ("fd_pwrite", "fd" = fd, "iovs_len" = iovs_len, "offset" = offset);
+    world::fd_read(fd: Fd, iovs_ptr: *const Iovec, iovs_len: usize, nread: *mut Size)
+        => ("fd_read", "fd" = fd, "iovs_len" = iovs_len);
+    fs::fd_readdir(fd: Fd, buf: *mut u8, buf_len: Size, cookie: Dircookie, bufused: *mut Size)
+        => ("fd_readdir", "fd" = fd, "buf_len" = buf_len, "cookie" = cookie);
+    world::fd_renumber(fd: Fd, to: Fd) => ("fd_renumber", "fd" = fd, "to" = to);
+    fs::fd_seek(fd: Fd, offset: Filedelta, whence: Whence, newoffset: *mut Filesize)
+        => ("fd_seek", "fd" = fd, "offset" = offset, "whence" = whence);
+    fs::fd_sync(fd: Fd) => ("fd_sync", "fd" = fd);
+    fs::fd_tell(fd: Fd, offset: *mut Filesize) => ("fd_tell", "fd" = fd);
+    world::fd_write(fd: Fd, iovs_ptr: *const Ciovec, iovs_len: usize, nwritten: *mut Size)
+        => ("fd_write", "fd" = fd, "iovs_len" = iovs_len);
+    fs::path_create_directory(fd: Fd, path_ptr: *const u8, path_len: usize)
+        => (
+            "path_create_directory",
+            "fd" = fd,
+            "path" = core::slice::from_raw_parts(path_ptr, path_len),
+        );
+    fs::path_filestat_get(
+        fd: Fd,
+        flags: Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut Filestat,
+    ) => (
+        "path_filestat_get",
+        "fd" = fd,
+        "flags" = flags,
+        "path" = core::slice::from_raw_parts(path_ptr, path_len),
+    );
+    fs::path_filestat_set_times(
+        fd: Fd,
+        flags: Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: Fstflags,
+    ) => (
+        "path_filestat_set_times",
+        "fd" = fd,
+        "flags" = flags,
+        "path" = core::slice::from_raw_parts(path_ptr, path_len),
+        "atim" = atim,
+        "mtim" = mtim,
+        "fst_flags" = fst_flags,
+    );
+    fs::path_link(
+        old_fd: Fd,
+        old_flags: Lookupflags,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_fd: Fd,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) => (
+        "path_link",
+        "old_fd" = old_fd,
+        "old_flags" = old_flags,
+        "old_path" = core::slice::from_raw_parts(old_path_ptr, old_path_len),
+        "new_fd" = new_fd,
+        "new_path" = core::slice::from_raw_parts(new_path_ptr, new_path_len),
+    );
+    fs::path_open(
+        fd: Fd,
+        dirflags: Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        oflags: Oflags,
+        fs_rights_base: Rights,
+        fs_rights_inheriting: Rights,
+        fdflags: Fdflags,
+        opened_fd: *mut Fd,
+    ) => (
+        "path_open",
+        "fd" = fd,
+        "dirflags" = dirflags,
+        "path" = core::slice::from_raw_parts(path_ptr, path_len),
+        "oflags" = oflags,
+        "fs_rights_base" = fs_rights_base,
+        "fs_rights_inheriting" = fs_rights_inheriting,
+        "fdflags" = fdflags,
+    );
+    fs::path_readlink(
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: Size,
+        bufused: *mut Size,
+    ) => (
+        "path_readlink",
+        "fd" = fd,
+        "path" = core::slice::from_raw_parts(path_ptr, path_len),
+        "buf_len" = buf_len,
+    );
+    fs::path_remove_directory(fd: Fd, path_ptr: *const u8, path_len: usize)
+        => (
+            "path_remove_directory",
+            "fd" = fd,
+            "path" = core::slice::from_raw_parts(path_ptr, path_len),
+        );
+    fs::path_rename(
+        old_fd: Fd,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_fd: Fd,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) => (
+        "path_rename",
+        "old_fd" = old_fd,
+        "old_path" = core::slice::from_raw_parts(old_path_ptr, old_path_len),
+        "new_fd" = new_fd,
+        "new_path" = core::slice::from_raw_parts(new_path_ptr, new_path_len),
+    );
+    fs::path_symlink(
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        fd: Fd,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) => (
+        "path_symlink",
+        "old_path" = core::slice::from_raw_parts(old_path_ptr, old_path_len),
+        "fd" = fd,
+        "new_path" = core::slice::from_raw_parts(new_path_ptr, new_path_len),
+    );
+    fs::path_unlink_file(fd: Fd, path_ptr: *const u8, path_len: usize)
+        => (
+            "path_unlink_file",
+            "fd" = fd,
+            "path" = core::slice::from_raw_parts(path_ptr, path_len),
+        );
+    world::poll_oneoff(
+        r#in: *const Subscription,
+        out: *mut Event,
+        nsubscriptions: Size,
+        nevents: *mut Size,
+    ) => ("poll_oneoff", "nsubscriptions" = nsubscriptions);
+    world::proc_raise(sig: Signal) => ("proc_raise", "sig" = sig);
+    shared::sched_yield() => ("sched_yield");
+    shared::random_get(buf: *mut u8, buf_len: Size) => ("random_get", "buf_len" = buf_len);
+    sock::sock_accept(fd: Fd, flags: Fdflags, connection: *mut Fd)
+        => ("sock_accept", "fd" = fd, "flags" = flags);
+    sock::sock_recv(
+        fd: Fd,
+        ri_data_ptr: *const Iovec,
+        ri_data_len: usize,
+        ri_flags: Riflags,
+        ro_datalen: *mut Size,
+        ro_flags: *mut Roflags,
+    ) => ("sock_recv", "fd" = fd, "ri_data_len" = ri_data_len, "ri_flags" = ri_flags);
+    sock::sock_send(
+        fd: Fd,
+        si_data_ptr: *const Ciovec,
+        si_data_len: usize,
+        si_flags: Siflags,
+        so_datalen: *mut Size,
+    ) => ("sock_send", "fd" = fd, "si_data_len" = si_data_len, "si_flags" = si_flags);
+    sock::sock_shutdown(fd: Fd, how: Sdflags) => ("sock_shutdown", "fd" = fd, "how" = how);
+}
+
+/// `proc_exit` doesn't return, so there's no errno to log.
+#[export_name = "proc_exit"]
+pub unsafe extern "C" fn proc_exit(rval: Exitcode) -> ! {
+    trace!("proc_exit", "rval" = rval);
+    world::proc_exit(rval)
+}
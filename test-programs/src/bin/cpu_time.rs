@@ -0,0 +1,21 @@
+fn main() {
+    for clock in [
+        wasi::CLOCKID_PROCESS_CPUTIME_ID,
+        wasi::CLOCKID_THREAD_CPUTIME_ID,
+    ] {
+        let resolution = unsafe { wasi::clock_res_get(clock) }.unwrap();
+        assert!(resolution > 0);
+
+        let start = unsafe { wasi::clock_time_get(clock, 1) }.unwrap();
+
+        // Burn some CPU time so the clock has to advance.
+        let mut x = 0u64;
+        for i in 0..10_000_000u64 {
+            x = std::hint::black_box(x.wrapping_mul(31).wrapping_add(i));
+        }
+        std::hint::black_box(x);
+
+        let end = unsafe { wasi::clock_time_get(clock, 1) }.unwrap();
+        assert!(end > start, "{end} > {start}");
+    }
+}
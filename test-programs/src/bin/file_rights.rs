@@ -0,0 +1,28 @@
+use std::{error::Error, fs::OpenOptions, io::Write, os::fd::AsRawFd};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new().write(true).open("bar.txt")?;
+    let fd = file.as_raw_fd() as wasi::Fd;
+
+    let stat = unsafe { wasi::fd_fdstat_get(fd) }?;
+    assert_ne!(stat.fs_rights_base & wasi::RIGHTS_FD_WRITE, 0);
+    file.write_all(b"before\n")?;
+
+    // Drop the right to write, and check that it's reported and enforced.
+    let base = stat.fs_rights_base & !wasi::RIGHTS_FD_WRITE;
+    unsafe { wasi::fd_fdstat_set_rights(fd, base, stat.fs_rights_inheriting) }?;
+    let rights = unsafe { wasi::fd_fdstat_get(fd) }?.fs_rights_base;
+    assert_eq!(rights & wasi::RIGHTS_FD_WRITE, 0);
+    let err = file.write_all(b"after\n").unwrap_err();
+    assert_eq!(
+        err.raw_os_error(),
+        Some(wasi::ERRNO_NOTCAPABLE.raw().into())
+    );
+
+    // Rights can't be added back.
+    let result =
+        unsafe { wasi::fd_fdstat_set_rights(fd, stat.fs_rights_base, stat.fs_rights_inheriting) };
+    assert_eq!(result, Err(wasi::ERRNO_NOTCAPABLE));
+
+    Ok(())
+}
@@ -0,0 +1,10 @@
+fn main() {
+    // Together these are several times larger than the adapter's state.
+    for i in 0..1000 {
+        let value = std::env::var(format!("VAR{i}")).unwrap();
+        assert_eq!(value, i.to_string().repeat(100));
+    }
+    let value = std::env::var("BIG").unwrap();
+    assert_eq!(value.len(), 200_000);
+    assert!(value.bytes().all(|b| b == b'x'));
+}
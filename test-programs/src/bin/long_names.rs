@@ -0,0 +1,74 @@
+use std::{collections::HashSet, error::Error, fs, mem::size_of, path::PathBuf};
+
+/// The preopened directory.
+const DIR_FD: wasi::Fd = 3;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // The host creates these, with names as long as common filesystems allow.
+    let mut expected = (0..3)
+        .map(|i| format!("{i}{}", "x".repeat(254)))
+        .collect::<HashSet<_>>();
+    expected.insert("short.txt".to_string());
+    expected.insert(".".to_string());
+    expected.insert("..".to_string());
+
+    // List the directory with buffers too small to hold every entry, so that
+    // `fd_readdir` has to truncate entries and resume from them later.
+    for buf_len in [size_of::<wasi::Dirent>() + 10, 300, 4096] {
+        assert_eq!(read_dir(buf_len)?, expected);
+    }
+
+    // A symlink target can be much longer than a single file name.
+    let target = "d/".repeat(1000) + "end";
+    unsafe { wasi::path_symlink(&target, DIR_FD, "link") }?;
+    assert_eq!(fs::read_link("/link")?, PathBuf::from(&target));
+
+    // Preview1 truncates the target, without an error, if it doesn't fit in
+    // the buffer. The whole buffer is used then, and nothing past it is written.
+    for buf_len in [1, 16, target.len() - 1] {
+        let mut buf = vec![0xff; buf_len + 1];
+        let len = unsafe { wasi::path_readlink(DIR_FD, "link", buf.as_mut_ptr(), buf_len) }?;
+        assert_eq!(len, buf_len);
+        assert_eq!(&buf[..len], &target.as_bytes()[..len]);
+        assert_eq!(buf[buf_len], 0xff);
+    }
+
+    // A buffer with room to spare gets the whole target, without a nul.
+    let mut buf = vec![0xff; target.len() + 16];
+    let len = unsafe { wasi::path_readlink(DIR_FD, "link", buf.as_mut_ptr(), buf.len()) }?;
+    assert_eq!(len, target.len());
+    assert_eq!(&buf[..len], target.as_bytes());
+    assert_eq!(buf[len], 0xff);
+
+    Ok(())
+}
+
+/// Read the names in the preopened directory the way wasi-libc does, growing
+/// the buffer when an entry doesn't fit.
+fn read_dir(buf_len: usize) -> Result<HashSet<String>, Box<dyn Error>> {
+    let mut buf = vec![0; buf_len];
+    let mut names = HashSet::new();
+    let mut cookie = wasi::DIRCOOKIE_START;
+    loop {
+        let used = unsafe { wasi::fd_readdir(DIR_FD, buf.as_mut_ptr(), buf.len(), cookie) }?;
+        let mut rest = &buf[..used];
+        let mut progress = false;
+        while rest.len() >= size_of::<wasi::Dirent>() {
+            let dirent = unsafe { rest.as_ptr().cast::<wasi::Dirent>().read_unaligned() };
+            let end = size_of::<wasi::Dirent>() + dirent.d_namlen as usize;
+            let Some(name) = rest.get(size_of::<wasi::Dirent>()..end) else {
+                break;
+            };
+            names.insert(String::from_utf8(name.to_vec())?);
+            cookie = dirent.d_next;
+            rest = &rest[end..];
+            progress = true;
+        }
+        if used < buf.len() {
+            return Ok(names);
+        }
+        if !progress {
+            buf.resize(buf.len() * 2, 0);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{Read, Seek, Write},
+};
+
+// More than the adapter keeps in its inline descriptor table.
+const FILES: usize = 300;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut files = Vec::new();
+    for i in 0..FILES {
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(format!("file{i}.txt"))?;
+        write!(file, "{i}")?;
+        files.push(file);
+    }
+
+    // All the files are still open, so check each reads back its own data.
+    for (i, file) in files.iter_mut().enumerate() {
+        file.rewind()?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        assert_eq!(contents, i.to_string());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant, SystemTime};
+
+const CLOCKID_REALTIME: u32 = 0;
+const EVENTTYPE_CLOCK: u8 = 0;
+const SUBCLOCKFLAGS_SUBSCRIPTION_CLOCK_ABSTIME: u16 = 1;
+
+#[repr(C)]
+struct SubscriptionClock {
+    id: u32,
+    timeout: u64,
+    precision: u64,
+    flags: u16,
+}
+
+#[repr(C)]
+struct Subscription {
+    userdata: u64,
+    tag: u8,
+    clock: SubscriptionClock,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Event {
+    userdata: u64,
+    error: u16,
+    type_: u8,
+    nbytes: u64,
+    flags: u16,
+}
+
+#[link(wasm_import_module = "wasi_snapshot_preview1")]
+extern "C" {
+    fn poll_oneoff(
+        subscriptions: *const Subscription,
+        events: *mut Event,
+        nsubscriptions: usize,
+        nevents: *mut usize,
+    ) -> u16;
+}
+
+fn poll_realtime(timeout: u64, flags: u16) {
+    let subscription = Subscription {
+        userdata: 42,
+        tag: EVENTTYPE_CLOCK,
+        clock: SubscriptionClock {
+            id: CLOCKID_REALTIME,
+            timeout,
+            precision: 0,
+            flags,
+        },
+    };
+    let mut event = Event::default();
+    let mut nevents = 0;
+    let errno = unsafe { poll_oneoff(&subscription, &mut event, 1, &mut nevents) };
+    assert_eq!(errno, 0);
+    assert_eq!(nevents, 1);
+    assert_eq!(event.userdata, 42);
+    assert_eq!(event.error, 0);
+    assert_eq!(event.type_, EVENTTYPE_CLOCK);
+}
+
+fn main() {
+    // A relative timeout.
+    let start = Instant::now();
+    poll_realtime(Duration::from_millis(10).as_nanos() as u64, 0);
+    assert!(start.elapsed() >= Duration::from_millis(10));
+
+    // An absolute deadline in the future.
+    let deadline = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        + Duration::from_millis(10);
+    poll_realtime(
+        deadline.as_nanos() as u64,
+        SUBCLOCKFLAGS_SUBSCRIPTION_CLOCK_ABSTIME,
+    );
+    assert!(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            >= deadline
+    );
+
+    // An absolute deadline which has already passed.
+    poll_realtime(1, SUBCLOCKFLAGS_SUBSCRIPTION_CLOCK_ABSTIME);
+}
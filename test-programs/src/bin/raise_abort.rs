@@ -0,0 +1,8 @@
+fn main() {
+    // `SIGCHLD` is ignored by default.
+    unsafe { wasi::proc_raise(wasi::SIGNAL_CHLD) }.unwrap();
+
+    // `SIGABRT` terminates the process.
+    let _ = unsafe { wasi::proc_raise(wasi::SIGNAL_ABRT) };
+    unreachable!("proc_raise(SIGABRT) returned");
+}
@@ -0,0 +1,32 @@
+//! Unlike `export_cabi_realloc`, this doesn't export `cabi_realloc`, so the
+//! adapter's memory comes from the fallback `wit-component` provides, which
+//! can only allocate a page at a time. Reading symlink targets which don't
+//! fit in the caller's buffer makes the adapter allocate, and then replace,
+//! the buffer it reads them into.
+use std::error::Error;
+
+/// The preopened directory.
+const DIR_FD: wasi::Fd = 3;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // Increasing lengths, so that each target outgrows the last one's buffer.
+    let targets = [10, 100, 1000, 4000].map(|len| "t".repeat(len));
+    for (i, target) in targets.iter().enumerate() {
+        unsafe { wasi::path_symlink(target, DIR_FD, &format!("link{i}")) }?;
+    }
+
+    // Read them twice, so the second round reuses the buffer from the first.
+    for _ in 0..2 {
+        for (i, target) in targets.iter().enumerate() {
+            let mut buf = [0xff; 9];
+            let len = unsafe {
+                wasi::path_readlink(DIR_FD, &format!("link{i}"), buf.as_mut_ptr(), buf.len() - 1)
+            }?;
+            assert_eq!(len, buf.len() - 1);
+            assert_eq!(&buf[..len], &target.as_bytes()[..len]);
+            assert_eq!(buf[len], 0xff);
+        }
+    }
+
+    Ok(())
+}
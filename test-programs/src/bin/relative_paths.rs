@@ -0,0 +1,19 @@
+use std::{error::Error, fs};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // `PWD` is `/work/sub`, so relative paths resolve there.
+    assert_eq!(fs::read_to_string("data.txt")?, "Twas brillig");
+    assert_eq!(
+        fs::read_to_string("data.txt")?,
+        fs::read_to_string("/work/sub/data.txt")?
+    );
+    assert_eq!(fs::read_to_string("../top.txt")?, "and the slithy toves");
+
+    fs::write("new.txt", "Did gyre and gimble")?;
+    assert_eq!(
+        fs::read_to_string("/work/sub/new.txt")?,
+        "Did gyre and gimble"
+    );
+
+    Ok(())
+}
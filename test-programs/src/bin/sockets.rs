@@ -0,0 +1,70 @@
+use wasi::{Ciovec, Fd, Iovec};
+
+/// The preopened listener, which follows stdio as there are no preopened
+/// directories.
+const LISTENER: Fd = 3;
+
+fn main() {
+    let listener = unsafe { wasi::fd_fdstat_get(LISTENER) }.unwrap();
+    assert_eq!(listener.fs_filetype, wasi::FILETYPE_SOCKET_STREAM);
+    assert_ne!(listener.fs_rights_base & wasi::RIGHTS_SOCK_ACCEPT, 0);
+
+    // Streams only have the rights which apply to streams.
+    for stat in [listener, unsafe { wasi::fd_fdstat_get(1) }.unwrap()] {
+        let rights = stat.fs_rights_base | stat.fs_rights_inheriting;
+        assert_eq!(rights & (wasi::RIGHTS_PATH_OPEN | wasi::RIGHTS_FD_SEEK), 0);
+    }
+
+    // An accepted connection gets the listener's inheriting rights.
+    let conn = unsafe { wasi::sock_accept(LISTENER, 0) }.unwrap();
+    let stat = unsafe { wasi::fd_fdstat_get(conn) }.unwrap();
+    assert_eq!(stat.fs_filetype, wasi::FILETYPE_SOCKET_STREAM);
+    assert_eq!(stat.fs_rights_base, listener.fs_rights_inheriting);
+    assert_eq!(stat.fs_rights_inheriting, listener.fs_rights_inheriting);
+
+    assert_eq!(recv_all(conn), b"hello");
+    send(conn, b"world").unwrap();
+    unsafe { wasi::sock_shutdown(conn, wasi::SDFLAGS_WR) }.unwrap();
+    unsafe { wasi::fd_close(conn) }.unwrap();
+
+    // Without `fd_write` in the listener's inheriting rights, the next
+    // connection can't send.
+    let inheriting = listener.fs_rights_inheriting & !wasi::RIGHTS_FD_WRITE;
+    unsafe { wasi::fd_fdstat_set_rights(LISTENER, listener.fs_rights_base, inheriting) }.unwrap();
+    let conn = unsafe { wasi::sock_accept(LISTENER, 0) }.unwrap();
+    let stat = unsafe { wasi::fd_fdstat_get(conn) }.unwrap();
+    assert_eq!(stat.fs_rights_base & wasi::RIGHTS_FD_WRITE, 0);
+
+    assert_eq!(recv_all(conn), b"hello");
+    assert_eq!(send(conn, b"world"), Err(wasi::ERRNO_NOTCAPABLE));
+    unsafe { wasi::sock_shutdown(conn, wasi::SDFLAGS_RD | wasi::SDFLAGS_WR) }.unwrap();
+    unsafe { wasi::fd_close(conn) }.unwrap();
+}
+
+/// Receive until the peer shuts down its side of the connection.
+fn recv_all(fd: Fd) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut buf = [0; 64];
+    loop {
+        let iovs = [Iovec {
+            buf: buf.as_mut_ptr(),
+            buf_len: buf.len(),
+        }];
+        let (n, flags) = unsafe { wasi::sock_recv(fd, &iovs, 0) }.unwrap();
+        assert_eq!(flags, 0);
+        if n == 0 {
+            return data;
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+}
+
+fn send(fd: Fd, data: &[u8]) -> Result<(), wasi::Errno> {
+    let iovs = [Ciovec {
+        buf: data.as_ptr(),
+        buf_len: data.len(),
+    }];
+    let n = unsafe { wasi::sock_send(fd, &iovs, 0) }?;
+    assert_eq!(n, data.len());
+    Ok(())
+}
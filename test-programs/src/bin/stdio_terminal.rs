@@ -0,0 +1,8 @@
+use std::io::IsTerminal;
+
+fn main() {
+    // The host connects all of the stdio streams to pipes.
+    assert!(!std::io::stdin().is_terminal());
+    assert!(!std::io::stdout().is_terminal());
+    assert!(!std::io::stderr().is_terminal());
+}
@@ -0,0 +1,15 @@
+use wasi::wasi_snapshot_preview1::{clock_res_get, clock_time_get};
+
+fn main() {
+    // An unknown clock id is reported as an error rather than trapping.
+    let mut timestamp = 0u64;
+    let ptr = &mut timestamp as *mut u64 as i32;
+    assert_eq!(
+        unsafe { clock_res_get(99, ptr) },
+        wasi::ERRNO_INVAL.raw().into()
+    );
+    assert_eq!(
+        unsafe { clock_time_get(99, 1, ptr) },
+        wasi::ERRNO_INVAL.raw().into()
+    );
+}
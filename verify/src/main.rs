@@ -27,6 +27,7 @@ fn main() -> Result<()> {
                         TypeRef::Func(_) => {
                             if i.module != "wall-clock"
                                 && i.module != "monotonic-clock"
+                                && i.module != "cpu-clock"
                                 && i.module != "instance-wall-clock"
                                 && i.module != "instance-monotonic-clock"
                                 && i.module != "timezone"
@@ -43,8 +44,11 @@ fn main() -> Result<()> {
                                 && i.module != "streams"
                                 && i.module != "environment"
                                 && i.module != "preopens"
+                                && i.module != "terminal-input"
+                                && i.module != "terminal-output"
                                 && i.module != "exit"
                                 && i.module != "stderr"
+                                && i.module != "console"
                                 && i.module != "types"
                                 && i.module != "default-outgoing-HTTP"
                                 && i.module != "HTTP"
@@ -1,7 +1,9 @@
 use cap_std::time::{Duration, Instant, SystemClock};
 use cap_std::{ambient_authority, AmbientAuthority};
 use cap_time_ext::{MonotonicClockExt, SystemClockExt};
-use wasi_common::clocks::{WasiClocks, WasiMonotonicClock, WasiWallClock};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use wasi_common::clocks::{WasiClocks, WasiCpuClock, WasiMonotonicClock, WasiWallClock};
 
 pub struct WallClock {
     /// The underlying system clock.
@@ -64,10 +66,138 @@ impl WasiMonotonicClock for MonotonicClock {
     }
 }
 
+/// Measures the CPU time of the thread the instance is running on, while it's
+/// running. Embedders which move a store to another thread while it's
+/// suspended in an async host call, or run other work on the thread meanwhile,
+/// get an approximation: time on a thread other than the one the clock started
+/// on isn't counted, as the two threads' CPU times can't be compared.
+pub struct CpuClock {
+    state: Mutex<CpuClockState>,
+}
+
+struct CpuClockState {
+    /// The CPU time used up to the last time the clock stopped.
+    used: u64,
+    /// The number of `start` calls not yet stopped.
+    depth: u32,
+    /// The thread the clock last started on, and its CPU time then.
+    started: Option<(ThreadId, u64)>,
+}
+
+impl CpuClockState {
+    /// Return the CPU time used since the clock started, if still on the same
+    /// thread.
+    fn running(&self) -> u64 {
+        match self.started {
+            Some((thread, started)) if thread == thread::current().id() => {
+                thread_cpu_time().saturating_sub(started)
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl CpuClock {
+    pub fn new(_ambient_authority: AmbientAuthority) -> Self {
+        Self {
+            state: Mutex::new(CpuClockState {
+                used: 0,
+                depth: 0,
+                started: None,
+            }),
+        }
+    }
+}
+
+impl WasiCpuClock for CpuClock {
+    #[cfg(unix)]
+    fn resolution(&self) -> u64 {
+        let res = rustix::time::clock_getres(rustix::time::ClockId::ThreadCPUTime);
+        (res.tv_sec as u64) * 1_000_000_000 + res.tv_nsec as u64
+    }
+
+    #[cfg(windows)]
+    fn resolution(&self) -> u64 {
+        use windows_sys::Win32::System::SystemInformation::GetSystemTimeAdjustment;
+
+        // Thread times are reported in 100-nanosecond units, but only advance
+        // once per clock interrupt.
+        let (mut adjustment, mut increment, mut disabled) = (0, 0, 0);
+        let ok = unsafe { GetSystemTimeAdjustment(&mut adjustment, &mut increment, &mut disabled) };
+        if ok == 0 || increment == 0 {
+            return 100;
+        }
+        u64::from(increment) * 100
+    }
+
+    fn now(&self) -> u64 {
+        let state = self.state.lock().unwrap();
+        state.used + state.running()
+    }
+
+    fn start(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.depth == 0 {
+            state.started = Some((thread::current().id(), thread_cpu_time()));
+        }
+        state.depth += 1;
+    }
+
+    fn stop(&self) {
+        let mut state = self.state.lock().unwrap();
+        // Call hooks may be unbalanced, e.g. when a trap unwinds through a host
+        // call. A stop without a start adds nothing, as `started` is `None`.
+        state.depth = state.depth.saturating_sub(1);
+        if state.depth == 0 {
+            state.used += state.running();
+            state.started = None;
+        }
+    }
+}
+
+/// Return the CPU time consumed by the current thread, in nanoseconds.
+#[cfg(unix)]
+fn thread_cpu_time() -> u64 {
+    let now = rustix::time::clock_gettime(rustix::time::ClockId::ThreadCPUTime);
+    (now.tv_sec as u64) * 1_000_000_000 + now.tv_nsec as u64
+}
+
+/// Return the CPU time consumed by the current thread, in nanoseconds.
+#[cfg(windows)]
+fn thread_cpu_time() -> u64 {
+    use windows_sys::Win32::Foundation::FILETIME;
+    use windows_sys::Win32::System::Threading::{GetCurrentThread, GetThreadTimes};
+
+    let zero = FILETIME {
+        dwLowDateTime: 0,
+        dwHighDateTime: 0,
+    };
+    let (mut creation, mut exit, mut kernel, mut user) = (zero, zero, zero, zero);
+    let ok = unsafe {
+        GetThreadTimes(
+            GetCurrentThread(),
+            &mut creation,
+            &mut exit,
+            &mut kernel,
+            &mut user,
+        )
+    };
+    if ok == 0 {
+        return 0;
+    }
+    let ticks = |t: FILETIME| (u64::from(t.dwHighDateTime) << 32) | u64::from(t.dwLowDateTime);
+    (ticks(kernel) + ticks(user)) * 100
+}
+
 pub fn clocks_ctx() -> WasiClocks {
     // Create the per-instance clock resources.
     let monotonic = Box::new(MonotonicClock::new(ambient_authority()));
     let wall = Box::new(WallClock::new(ambient_authority()));
+    let cpu = Box::new(CpuClock::new(ambient_authority()));
 
-    WasiClocks { monotonic, wall }
+    WasiClocks {
+        monotonic,
+        wall,
+        cpu,
+    }
 }
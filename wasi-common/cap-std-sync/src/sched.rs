@@ -36,7 +36,7 @@ pub async fn poll_oneoff<'a>(poll: &mut Poll<'a>) -> Result<(), Error> {
                 // sources to complete successfully.
                 if let Ok(nbytes) = stream.num_ready_bytes().await {
                     if nbytes != 0 {
-                        rwsub.complete(RwEventFlags::empty());
+                        rwsub.complete(nbytes, RwEventFlags::empty());
                         ready = true;
                         continue;
                     }
@@ -113,14 +113,23 @@ pub async fn poll_oneoff<'a>(poll: &mut Poll<'a>) -> Result<(), Error> {
             // were already completed due to being immediately available.
             for (rwsub, pollfd) in poll.rw_subscriptions().zip(pollfds.into_iter()) {
                 let revents = pollfd.revents();
+                if revents.is_empty() {
+                    continue;
+                }
+
+                // Only input streams know how many bytes are ready.
+                let nbytes = match rwsub.stream {
+                    RwStream::Read(stream) => stream.num_ready_bytes().await.unwrap_or(0),
+                    RwStream::Write(_) | RwStream::TcpSocket(_) => 0,
+                };
                 if revents.contains(PollFlags::NVAL) {
                     rwsub.error(Error::badf());
                 } else if revents.contains(PollFlags::ERR) {
                     rwsub.error(Error::io());
                 } else if revents.contains(PollFlags::HUP) {
-                    rwsub.complete(RwEventFlags::HANGUP);
+                    rwsub.complete(nbytes, RwEventFlags::HANGUP);
                 } else {
-                    rwsub.complete(RwEventFlags::empty());
+                    rwsub.complete(nbytes, RwEventFlags::empty());
                 };
             }
         }
@@ -1,3 +1,4 @@
+use is_terminal::IsTerminal;
 use std::any::Any;
 use std::convert::TryInto;
 use std::io;
@@ -11,10 +12,39 @@ use io_lifetimes::{AsFd, BorrowedFd};
 #[cfg(windows)]
 use io_lifetimes::{AsHandle, BorrowedHandle};
 use wasi_common::{
+    file::FileType,
     stream::{InputStream, OutputStream},
     Error, ErrorExt,
 };
 
+/// Return the type of what a stdio stream is connected to.
+#[cfg(unix)]
+fn stdio_filetype(stream: &impl AsFd) -> Result<FileType, Error> {
+    use rustix::fs::FileType as RawFileType;
+    let stat = rustix::fs::fstat(stream).map_err(std::io::Error::from)?;
+    Ok(match RawFileType::from_raw_mode(stat.st_mode) {
+        RawFileType::RegularFile => FileType::RegularFile,
+        RawFileType::Directory => FileType::Directory,
+        RawFileType::Symlink => FileType::SymbolicLink,
+        RawFileType::Fifo => FileType::Pipe,
+        RawFileType::Socket => FileType::SocketStream,
+        RawFileType::CharacterDevice => FileType::CharacterDevice,
+        RawFileType::BlockDevice => FileType::BlockDevice,
+        RawFileType::Unknown => FileType::Unknown,
+    })
+}
+
+/// Return the type of what a stdio stream is connected to. Only consoles are
+/// recognized on Windows.
+#[cfg(windows)]
+fn stdio_filetype(stream: &impl IsTerminal) -> Result<FileType, Error> {
+    if stream.is_terminal() {
+        Ok(FileType::CharacterDevice)
+    } else {
+        Ok(FileType::Unknown)
+    }
+}
+
 pub struct Stdin(std::io::Stdin);
 
 pub fn stdin() -> Stdin {
@@ -26,6 +56,12 @@ impl InputStream for Stdin {
     fn as_any(&self) -> &dyn Any {
         self
     }
+    async fn get_filetype(&self) -> Result<FileType, Error> {
+        stdio_filetype(&self.0)
+    }
+    fn isatty(&self) -> bool {
+        self.0.is_terminal()
+    }
     #[cfg(unix)]
     fn pollable_read(&self) -> Option<rustix::fd::BorrowedFd> {
         Some(self.0.as_fd())
@@ -101,6 +137,23 @@ macro_rules! wasi_file_write_impl {
                 self
             }
 
+            async fn get_filetype(&self) -> Result<FileType, Error> {
+                stdio_filetype(&self.0)
+            }
+
+            fn isatty(&self) -> bool {
+                self.0.is_terminal()
+            }
+
+            #[cfg(unix)]
+            fn window_size(&self) -> Option<(u16, u16)> {
+                if !self.0.is_terminal() {
+                    return None;
+                }
+                let size = rustix::termios::tcgetwinsize(&self.0).ok()?;
+                Some((size.ws_col, size.ws_row))
+            }
+
             #[cfg(unix)]
             fn pollable_write(&self) -> Option<rustix::fd::BorrowedFd> {
                 Some(self.0.as_fd())
@@ -0,0 +1,51 @@
+use cap_std::ambient_authority;
+use wasi_cap_std_sync::clocks::CpuClock;
+use wasi_common::clocks::WasiCpuClock;
+
+fn burn_cpu() {
+    let mut x = 0u64;
+    for i in 0..10_000_000u64 {
+        x = std::hint::black_box(x.wrapping_mul(31).wrapping_add(i));
+    }
+    std::hint::black_box(x);
+}
+
+#[test]
+fn only_runs_while_started() {
+    let clock = CpuClock::new(ambient_authority());
+    burn_cpu();
+    assert_eq!(clock.now(), 0);
+
+    // Nested starts keep the clock running until the outermost one stops.
+    clock.start();
+    clock.start();
+    burn_cpu();
+    clock.stop();
+    burn_cpu();
+    clock.stop();
+    let used = clock.now();
+    assert!(used > 0);
+
+    burn_cpu();
+    assert_eq!(clock.now(), used);
+    assert!(clock.resolution() > 0);
+}
+
+#[test]
+fn tolerates_unbalanced_stops_and_other_threads() {
+    let clock = CpuClock::new(ambient_authority());
+
+    // A stop without a start is ignored.
+    clock.stop();
+    assert_eq!(clock.now(), 0);
+
+    // Time on another thread than the one the clock started on isn't counted.
+    clock.start();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            burn_cpu();
+            clock.stop();
+        });
+    });
+    assert_eq!(clock.now(), 0);
+}
@@ -10,7 +10,20 @@ pub trait WasiMonotonicClock: Send + Sync {
     fn now(&self) -> u64;
 }
 
+/// A clock measuring the CPU time consumed by an instance, in nanoseconds.
+///
+/// The clock only advances between calls to `start` and `stop`, which the
+/// embedder makes as the instance starts and stops running on a thread. These
+/// calls may nest, and the clock runs until the outermost `start` is stopped.
+pub trait WasiCpuClock: Send + Sync {
+    fn resolution(&self) -> u64;
+    fn now(&self) -> u64;
+    fn start(&self);
+    fn stop(&self);
+}
+
 pub struct WasiClocks {
     pub wall: Box<dyn WasiWallClock + Send + Sync>,
     pub monotonic: Box<dyn WasiMonotonicClock + Send + Sync>,
+    pub cpu: Box<dyn WasiCpuClock + Send + Sync>,
 }
@@ -20,6 +20,8 @@ pub struct WasiCtx {
     pub env: Vec<(String, String)>,
     pub args: Vec<String>,
     pub preopens: Vec<(Box<dyn WasiDir>, String)>,
+    /// Table indices of the sockets inserted with `insert_listener`.
+    pub preopened_sockets: Vec<u32>,
     pub pool: Pool,
     pub network_creator: Box<dyn Fn(Pool) -> Result<Box<dyn WasiNetwork>, Error> + Send + Sync>,
     pub tcp_socket_creator:
@@ -45,6 +47,7 @@ impl WasiCtx {
             env: Vec::new(),
             args: Vec::new(),
             preopens: Vec::new(),
+            preopened_sockets: Vec::new(),
             pool: Pool::new(),
             network_creator,
             tcp_socket_creator,
@@ -69,6 +72,7 @@ impl WasiCtx {
 
     pub fn insert_listener(&mut self, fd: u32, listener: Box<dyn WasiTcpSocket>) {
         self.table_mut().insert_at(fd, Box::new(listener));
+        self.preopened_sockets.push(fd);
     }
 
     pub fn push_file(&mut self, file: Box<dyn WasiFile>) -> Result<u32, Error> {
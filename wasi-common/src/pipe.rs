@@ -7,6 +7,7 @@
 //! Some convenience constructors are included for common backing types like `Vec<u8>` and `String`,
 //! but the virtual pipes can be instantiated with any `Read` or `Write` type.
 //!
+use crate::file::FileType;
 use crate::stream::{InputStream, OutputStream};
 use crate::Error;
 use std::any::Any;
@@ -111,6 +112,10 @@ impl<R: Read + ReadReady + Any + Send + Sync> InputStream for ReadPipe<R> {
         self
     }
 
+    async fn get_filetype(&self) -> Result<FileType, Error> {
+        Ok(FileType::Pipe)
+    }
+
     async fn num_ready_bytes(&self) -> Result<u64, Error> {
         Ok(self.borrow().num_ready_bytes()?)
     }
@@ -213,6 +218,10 @@ impl<W: Write + Any + Send + Sync> OutputStream for WritePipe<W> {
         self
     }
 
+    async fn get_filetype(&self) -> Result<FileType, Error> {
+        Ok(FileType::Pipe)
+    }
+
     async fn write(&mut self, buf: &[u8]) -> Result<u64, Error> {
         let n = self.borrow().write(buf)?;
         Ok(n.try_into()?)
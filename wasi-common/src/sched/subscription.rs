@@ -19,7 +19,7 @@ pub enum RwStream<'a> {
 
 pub struct RwSubscription<'a> {
     pub stream: RwStream<'a>,
-    status: Option<Result<RwEventFlags, Error>>,
+    status: Option<Result<(u64, RwEventFlags), Error>>,
 }
 
 impl<'a> RwSubscription<'a> {
@@ -41,13 +41,15 @@ impl<'a> RwSubscription<'a> {
             status: None,
         }
     }
-    pub fn complete(&mut self, flags: RwEventFlags) {
-        self.status = Some(Ok(flags))
+    /// Mark the subscription as ready, with `size` bytes which can be read or
+    /// written without blocking, or zero if that isn't known.
+    pub fn complete(&mut self, size: u64, flags: RwEventFlags) {
+        self.status = Some(Ok((size, flags)))
     }
     pub fn error(&mut self, error: Error) {
         self.status = Some(Err(error))
     }
-    pub fn result(&mut self) -> Option<Result<RwEventFlags, Error>> {
+    pub fn result(&mut self) -> Option<Result<(u64, RwEventFlags), Error>> {
         self.status.take()
     }
     pub fn is_complete(&self) -> bool {
@@ -83,7 +85,7 @@ pub enum Subscription<'a> {
 
 #[derive(Debug)]
 pub enum SubscriptionResult {
-    ReadWrite(Result<RwEventFlags, Error>),
+    ReadWrite(Result<(u64, RwEventFlags), Error>),
     MonotonicClock(Result<(), Error>),
 }
 
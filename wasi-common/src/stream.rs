@@ -1,3 +1,4 @@
+use crate::file::FileType;
 use crate::{Error, ErrorExt};
 use std::any::Any;
 
@@ -24,6 +25,16 @@ pub trait InputStream: Send + Sync {
         None
     }
 
+    /// Return the type of what this stream is reading from, if it's known.
+    async fn get_filetype(&self) -> Result<FileType, Error> {
+        Ok(FileType::Unknown)
+    }
+
+    /// Test whether this stream is reading from a terminal.
+    fn isatty(&self) -> bool {
+        false
+    }
+
     /// Read bytes. On success, returns a pair holding the number of bytes read
     /// and a flag indicating whether the end of the stream was reached.
     async fn read(&mut self, _buf: &mut [u8]) -> Result<(u64, bool), Error> {
@@ -94,6 +105,22 @@ pub trait OutputStream: Send + Sync {
         None
     }
 
+    /// Return the type of what this stream is writing to, if it's known.
+    async fn get_filetype(&self) -> Result<FileType, Error> {
+        Ok(FileType::Unknown)
+    }
+
+    /// Test whether this stream is writing to a terminal.
+    fn isatty(&self) -> bool {
+        false
+    }
+
+    /// If this stream is writing to a terminal, return the size of its window
+    /// as a number of columns and rows, if that's known.
+    fn window_size(&self) -> Option<(u16, u16)> {
+        None
+    }
+
     /// Write bytes. On success, returns the number of bytes written.
     async fn write(&mut self, _buf: &[u8]) -> Result<u64, Error> {
         Err(Error::badf())